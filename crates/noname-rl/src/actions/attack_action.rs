@@ -0,0 +1,64 @@
+use bevy::{ecs::system::Command, prelude::*};
+
+use crate::{display_name, spend_time, DamageAction, EffectiveStats, LogCategory, LogMessage};
+
+/// Melee attack: the damage dealt is the attacker's effective attack
+/// reduced by the target's effective defense.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttackAction {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+impl AttackAction {
+    pub fn damage(attacker: &EffectiveStats, target: &EffectiveStats) -> i32 {
+        (attacker.attack - target.defense).max(0)
+    }
+
+    /// deals the damage, without spending the attacker's time: shots are
+    /// paid for when fired (see `ProjectileAction`)
+    pub fn hit(self, world: &mut World) {
+        let (attacker, target) = match (
+            world.get::<EffectiveStats>(self.attacker),
+            world.get::<EffectiveStats>(self.target),
+        ) {
+            (Some(attacker), Some(target)) => (*attacker, *target),
+            _ => return,
+        };
+
+        let amount = Self::damage(&attacker, &target);
+        info!(
+            "AttackAction: {:?} hits {:?} for {} damage",
+            self.attacker, self.target, amount
+        );
+        let attacker_name = display_name(world, self.attacker);
+        let verb = if attacker_name == "you" {
+            "hit"
+        } else {
+            "hits"
+        };
+        LogMessage::new(
+            LogCategory::Combat,
+            format!(
+                "{} {} {}",
+                attacker_name,
+                verb,
+                display_name(world, self.target)
+            ),
+        )
+        .apply(world);
+        DamageAction {
+            entity: self.target,
+            amount,
+            source: Some(self.attacker),
+        }
+        .apply(world);
+    }
+}
+
+impl Command for AttackAction {
+    fn apply(self, world: &mut World) {
+        spend_time(world, self.attacker, 1);
+        self.hit(world);
+    }
+}
@@ -0,0 +1,38 @@
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    effects::prelude::SoundKind, events::SoundEvent, spend_time, DoorState, GameMap,
+    NeedsFovUpdate, Player,
+};
+
+/// `entity` opens or closes the door at `target_tile`, which takes a turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoorAction {
+    pub entity: Entity,
+    pub target_tile: TilePos,
+    pub state: DoorState,
+}
+
+impl Command for DoorAction {
+    fn apply(self, world: &mut World) {
+        info!("DoorAction: {:?}", self);
+        world
+            .resource_mut::<GameMap>()
+            .set_door(&self.target_tile, self.state);
+        spend_time(world, self.entity, 1);
+        world.send_event(SoundEvent {
+            kind: SoundKind::Door,
+            position: Some(self.target_tile),
+        });
+
+        // an opened/closed door changes what the player can see
+        let players = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for player in players {
+            world.entity_mut(player).insert(NeedsFovUpdate);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::{ecs::system::Command, prelude::*, utils::HashSet};
+use bevy_ecs_tilemap::tiles::TilePos;
+use rand::seq::SliceRandom;
+
+use crate::{
+    events::{DamageEvent, ScreenShakeEvent},
+    resources::GameRng,
+    spend_time, EffectiveStats, GameMap, Health, Inventory, Invulnerable, NeedsFovUpdate, TileKind,
+};
+
+/// Removes one item from an inventory slot: using an item takes a turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsumeItemAction {
+    pub entity: Entity,
+    pub slot: usize,
+}
+
+impl Command for ConsumeItemAction {
+    fn apply(self, world: &mut World) {
+        if let Some(mut inventory) = world.get_mut::<Inventory>(self.entity) {
+            inventory.take(self.slot, 1);
+        }
+        spend_time(world, self.entity, 1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealAction {
+    pub entity: Entity,
+    pub amount: i32,
+}
+
+impl Command for HealAction {
+    fn apply(self, world: &mut World) {
+        let max_health = match world.get::<EffectiveStats>(self.entity) {
+            Some(stats) => stats.max_health,
+            None => return,
+        };
+        if let Some(mut health) = world.get_mut::<Health>(self.entity) {
+            health.current = (health.current + self.amount).min(max_health);
+            info!("HealAction: {:?} heals to {}", self.entity, health.current);
+        }
+    }
+}
+
+/// Removes health from an entity. `source` is whoever caused the damage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageAction {
+    pub entity: Entity,
+    pub amount: i32,
+    pub source: Option<Entity>,
+}
+
+impl Command for DamageAction {
+    fn apply(self, world: &mut World) {
+        // god mode of the debug console
+        if world.get::<Invulnerable>(self.entity).is_some() {
+            info!("DamageAction: {:?} is invulnerable", self.entity);
+            return;
+        }
+        if let Some(mut health) = world.get_mut::<Health>(self.entity) {
+            health.current -= self.amount;
+            info!(
+                "DamageAction: {:?} takes {} damage ({} left)",
+                self.entity, self.amount, health.current
+            );
+            world.send_event(DamageEvent {
+                target: self.entity,
+                amount: self.amount,
+                source: self.source,
+            });
+        }
+    }
+}
+
+/// tiles holding an actor
+pub fn occupied_tiles(world: &mut World) -> HashSet<TilePos> {
+    world
+        .query_filtered::<&TilePos, With<Health>>()
+        .iter(world)
+        .copied()
+        .collect()
+}
+
+/// Moves an entity to `target`, or to a random free floor tile, without
+/// animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeleportAction {
+    pub entity: Entity,
+    pub target: Option<TilePos>,
+}
+
+impl Command for TeleportAction {
+    fn apply(self, world: &mut World) {
+        let tile_pos = match self.target {
+            Some(tile_pos) => tile_pos,
+            None => {
+                let occupied = occupied_tiles(world);
+                let floor_tiles = world
+                    .resource::<GameMap>()
+                    .tiles_of_kind(TileKind::Floor)
+                    .into_iter()
+                    .filter(|tile_pos| !occupied.contains(tile_pos))
+                    .collect::<Vec<_>>();
+                match floor_tiles.choose(&mut world.resource_mut::<GameRng>().rng) {
+                    Some(tile_pos) => *tile_pos,
+                    None => return,
+                }
+            }
+        };
+        let pos = world.resource::<GameMap>().tile_to_world(&tile_pos);
+        info!("TeleportAction: {:?} to {:?}", self.entity, tile_pos);
+
+        if let Some(mut transform) = world.get_mut::<Transform>(self.entity) {
+            transform.translation.x = pos.x;
+            transform.translation.y = pos.y;
+        }
+        if let Some(mut entity_pos) = world.get_mut::<TilePos>(self.entity) {
+            *entity_pos = tile_pos;
+        }
+        world.entity_mut(self.entity).insert(NeedsFovUpdate);
+    }
+}
+
+/// Marks every tile of the map as visited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevealMapAction;
+
+impl Command for RevealMapAction {
+    fn apply(self, world: &mut World) {
+        info!("RevealMapAction");
+        world.resource_mut::<GameMap>().reveal_all();
+    }
+}
+
+/// Damages every actor within `radius` tiles of `center`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExplodeAction {
+    pub center: TilePos,
+    pub radius: u32,
+    pub damage: i32,
+    pub source: Option<Entity>,
+}
+
+impl Command for ExplodeAction {
+    fn apply(self, world: &mut World) {
+        info!("ExplodeAction: {:?}", self);
+        world.send_event(ScreenShakeEvent { trauma: 0.6 });
+        let targets = world
+            .query_filtered::<(Entity, &TilePos), With<Health>>()
+            .iter(world)
+            .filter(|(_, pos)| {
+                pos.x.abs_diff(self.center.x) <= self.radius
+                    && pos.y.abs_diff(self.center.y) <= self.radius
+            })
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+
+        for entity in targets {
+            DamageAction {
+                entity,
+                amount: self.damage,
+                source: self.source,
+            }
+            .apply(world);
+        }
+    }
+}
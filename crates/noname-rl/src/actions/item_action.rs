@@ -0,0 +1,233 @@
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    display_name, effects::prelude::SoundKind, events::SoundEvent, spend_time, Equipment,
+    GameEntity, GameMap, Inventory, Item, ItemTable, LogCategory, LogMessage, MyAssets,
+};
+
+/// Moves the item entity `item` into the inventory of `entity`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickUpAction {
+    pub entity: Entity,
+    pub item: Entity,
+}
+
+impl Command for PickUpAction {
+    fn apply(self, world: &mut World) {
+        let item = match world.get::<Item>(self.item) {
+            Some(item) => item.clone(),
+            None => return,
+        };
+        let definition = match world.resource::<ItemTable>().get(&item.id) {
+            Some(definition) => definition.clone(),
+            None => {
+                warn!("unknown item {:?}", item.id);
+                return;
+            }
+        };
+
+        let left = match world.get_mut::<Inventory>(self.entity) {
+            Some(mut inventory) => inventory.add(&definition, item.count),
+            None => return,
+        };
+        if left == item.count {
+            info!("PickUpAction: no room for {}", definition.name);
+            return;
+        }
+        spend_time(world, self.entity, 1);
+        info!(
+            "PickUpAction: {:?} picks up {} {}",
+            self.entity,
+            item.count - left,
+            definition.name
+        );
+        LogMessage::new(
+            LogCategory::Item,
+            format!(
+                "{} picked up {} {}",
+                display_name(world, self.entity),
+                item.count - left,
+                definition.name
+            ),
+        )
+        .apply(world);
+        world.send_event(SoundEvent {
+            kind: SoundKind::PickUp,
+            position: world.get::<TilePos>(self.entity).copied(),
+        });
+
+        if left == 0 {
+            world.entity_mut(self.item).despawn_recursive();
+        } else if let Some(mut item) = world.get_mut::<Item>(self.item) {
+            item.count = left;
+        }
+    }
+}
+
+/// Takes the stack in `slot` out of the inventory of `entity` and leaves it
+/// on the tile the entity is standing on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropAction {
+    pub entity: Entity,
+    pub slot: usize,
+}
+
+impl Command for DropAction {
+    fn apply(self, world: &mut World) {
+        let tile_pos = match world.get::<TilePos>(self.entity) {
+            Some(tile_pos) => *tile_pos,
+            None => return,
+        };
+        let item = match world.get_mut::<Inventory>(self.entity) {
+            Some(mut inventory) => match inventory.take(self.slot, u32::MAX) {
+                Some(item) => item,
+                None => return,
+            },
+            None => return,
+        };
+        info!("DropAction: {:?} drops {:?}", self.entity, item);
+        spend_time(world, self.entity, 1);
+        LogMessage::new(
+            LogCategory::Item,
+            format!(
+                "{} dropped {} {}",
+                display_name(world, self.entity),
+                item.count,
+                item_name(world, &item)
+            ),
+        )
+        .apply(world);
+
+        spawn_item(world, item, tile_pos);
+    }
+}
+
+/// Wears one item of the inventory stack in `slot`. An item replaced by
+/// it goes back to the inventory, or to the floor if there is no room.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquipAction {
+    pub entity: Entity,
+    pub slot: usize,
+}
+
+impl Command for EquipAction {
+    fn apply(self, world: &mut World) {
+        let item = match world.get_mut::<Inventory>(self.entity) {
+            Some(mut inventory) => match inventory.take(self.slot, 1) {
+                Some(item) => item,
+                None => return,
+            },
+            None => return,
+        };
+        let equip_slot = match world
+            .resource::<ItemTable>()
+            .get(&item.id)
+            .and_then(|d| d.equip)
+        {
+            Some(equip) => equip.slot,
+            None => return,
+        };
+        info!("EquipAction: {:?} equips {:?}", self.entity, item);
+        spend_time(world, self.entity, 1);
+        LogMessage::new(
+            LogCategory::Item,
+            format!(
+                "{} equipped the {}",
+                display_name(world, self.entity),
+                item_name(world, &item)
+            ),
+        )
+        .apply(world);
+
+        let replaced = match world.get_mut::<Equipment>(self.entity) {
+            Some(mut equipment) => equipment.equip(equip_slot, item),
+            None => return,
+        };
+        if let Some(replaced) = replaced {
+            store_or_drop(world, self.entity, replaced);
+        }
+    }
+}
+
+/// Takes off the item at `index` of the equipment of `entity`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnequipAction {
+    pub entity: Entity,
+    pub index: usize,
+}
+
+impl Command for UnequipAction {
+    fn apply(self, world: &mut World) {
+        let item = match world.get_mut::<Equipment>(self.entity) {
+            Some(mut equipment) => match equipment.unequip(self.index) {
+                Some(item) => item,
+                None => return,
+            },
+            None => return,
+        };
+        info!("UnequipAction: {:?} takes off {:?}", self.entity, item);
+        spend_time(world, self.entity, 1);
+        LogMessage::new(
+            LogCategory::Item,
+            format!(
+                "{} took off the {}",
+                display_name(world, self.entity),
+                item_name(world, &item)
+            ),
+        )
+        .apply(world);
+
+        store_or_drop(world, self.entity, item);
+    }
+}
+
+fn item_name(world: &World, item: &Item) -> String {
+    world
+        .resource::<ItemTable>()
+        .get(&item.id)
+        .map_or(item.id.clone(), |definition| definition.name.clone())
+}
+
+/// Puts `item` into the inventory of `entity`, dropping what doesn't fit.
+fn store_or_drop(world: &mut World, entity: Entity, mut item: Item) {
+    let definition = match world.resource::<ItemTable>().get(&item.id) {
+        Some(definition) => definition.clone(),
+        None => return,
+    };
+    if let Some(mut inventory) = world.get_mut::<Inventory>(entity) {
+        item.count = inventory.add(&definition, item.count);
+    }
+    if item.count > 0 {
+        if let Some(tile_pos) = world.get::<TilePos>(entity).copied() {
+            spawn_item(world, item, tile_pos);
+        }
+    }
+}
+
+/// Spawns an item entity lying on the map.
+pub fn spawn_item(world: &mut World, item: Item, tile_pos: TilePos) -> Option<Entity> {
+    let sprite = world.resource::<ItemTable>().get(&item.id)?.sprite;
+    let pos = world.resource::<GameMap>().tile_to_world(&tile_pos);
+    let texture_atlas = world.resource::<MyAssets>().sprites.clone();
+
+    let entity = world
+        .spawn((
+            SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite {
+                    index: sprite,
+                    custom_size: Some(Vec2::new(16., 16.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(pos.x, pos.y, 4.0),
+                ..Default::default()
+            },
+            Name::new(item.id.clone()),
+            item,
+            tile_pos,
+            GameEntity,
+        ))
+        .id();
+    Some(entity)
+}
@@ -6,10 +6,38 @@ use bevy_ecs_tilemap::{
 use bevy_tweening::{lens::TransformPositionLens, Animator, EaseFunction, Tween, TweenCompleted};
 
 use crate::{
-    algorithms::tile_pos_to_world_pos, events::IntentionEndEvent, NeedsFovUpdate,
-    TileMapEntityLayer,
+    algorithms::tile_pos_to_world_pos,
+    effects::prelude::SoundKind,
+    events::{IntentionEndEvent, SoundEvent},
+    resources::{RLTimeSystem, TICKS_PER_TURN},
+    GameMap, NeedsFovUpdate, StatusEffects, TerrainEffect, TileMapEntityLayer,
 };
 
+mod attack_action;
+mod door_action;
+mod effect_action;
+mod item_action;
+mod projectile_action;
+mod status_action;
+pub use attack_action::*;
+pub use door_action::*;
+pub use effect_action::*;
+pub use item_action::*;
+pub use projectile_action::*;
+pub use status_action::*;
+
+/// Keeps `entity` busy for an action taking `cost` turns. Slowed and hasted
+/// actors need more or less time.
+pub fn spend_time(world: &mut World, entity: Entity, cost: u32) {
+    let ticks = match world.get::<StatusEffects>(entity) {
+        Some(effects) => effects.scale_time(cost),
+        None => cost * TICKS_PER_TURN,
+    };
+    world
+        .resource_mut::<RLTimeSystem>()
+        .schedule_entity(entity, ticks);
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct MoveAction {
     pub target_tile: TilePos,
@@ -82,6 +110,28 @@ impl Command for MoveAction {
         }
         {
             world.entity_mut(self.entity).insert(NeedsFovUpdate);
+            world.send_event(SoundEvent {
+                kind: SoundKind::Step,
+                position: Some(self.target_tile),
+            });
+        }
+        {
+            let terrain = world.resource::<GameMap>().terrain(&self.target_tile);
+
+            spend_time(world, self.entity, terrain.movement_cost);
+
+            match terrain.on_enter {
+                Some(TerrainEffect::Damage(amount)) => {
+                    info!("{:?} steps on {}", self.entity, terrain.name);
+                    DamageAction {
+                        entity: self.entity,
+                        amount,
+                        source: None,
+                    }
+                    .apply(world);
+                }
+                None => {}
+            }
         }
     }
 }
@@ -0,0 +1,145 @@
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_tweening::{lens::TransformPositionLens, Animator, EaseFunction, Tween, TweenCompleted};
+
+use crate::{
+    effects::prelude::SoundKind, events::SoundEvent, spend_time, ApplyStatusAction, AttackAction,
+    DamageAction, GameEntity, GameMap, MyAssets, StatusInflict,
+};
+
+/// sprite of a flying projectile
+const PROJECTILE_SPRITE: usize = 566;
+
+/// flight time per crossed tile
+const PROJECTILE_MS_PER_TILE: u64 = 30;
+
+/// How a projectile hurts what it hits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectileHit {
+    /// a weapon shot, resolved like a melee `AttackAction`
+    Attack,
+    /// fixed damage, ignoring defense (spells)
+    Damage(i32),
+}
+
+/// A projectile in flight. Its damage is applied when the flight animation
+/// ends (see `projectile_hit`).
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Projectile {
+    pub source: Entity,
+    pub target: Option<Entity>,
+    pub hit: ProjectileHit,
+    pub status: Option<StatusInflict>,
+}
+
+/// Shoots a projectile from `source` along `path` (see
+/// `GameMap::projectile_path`). `target` is the actor standing at the end of
+/// the path, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectileAction {
+    pub source: Entity,
+    pub path: Vec<TilePos>,
+    pub target: Option<Entity>,
+    pub hit: ProjectileHit,
+    /// status inflicted on the target when hit
+    pub status: Option<StatusInflict>,
+}
+
+impl Command for ProjectileAction {
+    fn apply(self, world: &mut World) {
+        info!("ProjectileAction: {:?}", self);
+        let end_tile = match self.path.last() {
+            Some(end_tile) => *end_tile,
+            None => return,
+        };
+        spend_time(world, self.source, 1);
+        let start = match world.get::<Transform>(self.source) {
+            Some(transform) => transform.translation.truncate(),
+            None => return,
+        };
+        let end = world.resource::<GameMap>().tile_to_world(&end_tile);
+        let texture_atlas = world.resource::<MyAssets>().sprites.clone();
+
+        let tween = Tween::new(
+            EaseFunction::Linear,
+            std::time::Duration::from_millis(PROJECTILE_MS_PER_TILE * self.path.len() as u64),
+            TransformPositionLens {
+                start: start.extend(7.),
+                end: end.extend(7.),
+            },
+        )
+        .with_completed_event(0);
+
+        world.spawn((
+            SpriteSheetBundle {
+                texture_atlas,
+                sprite: TextureAtlasSprite {
+                    index: PROJECTILE_SPRITE,
+                    custom_size: Some(Vec2::new(16., 16.)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(start.extend(7.)),
+                ..Default::default()
+            },
+            Name::new("Projectile"),
+            Projectile {
+                source: self.source,
+                target: self.target,
+                hit: self.hit,
+                status: self.status,
+            },
+            Animator::new(tween),
+            GameEntity,
+        ));
+    }
+}
+
+/// Applies the damage of the projectiles that reached their destination.
+pub fn projectile_hit(
+    mut reader: EventReader<TweenCompleted>,
+    projectiles_q: Query<(&Projectile, &Transform)>,
+    map: Res<GameMap>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut commands: Commands,
+) {
+    for ev in reader.iter() {
+        let (projectile, transform) = match projectiles_q.get(ev.entity) {
+            Ok((projectile, transform)) => (*projectile, transform),
+            Err(_) => continue,
+        };
+        commands.entity(ev.entity).despawn_recursive();
+
+        let target = match projectile.target {
+            Some(target) => target,
+            None => {
+                sound_events.send(SoundEvent {
+                    kind: SoundKind::Miss,
+                    position: map.world_to_tile(&transform.translation.truncate()),
+                });
+                continue;
+            }
+        };
+        match projectile.hit {
+            ProjectileHit::Attack => {
+                let attack = AttackAction {
+                    attacker: projectile.source,
+                    target,
+                };
+                commands.add(move |world: &mut World| attack.hit(world));
+            }
+            ProjectileHit::Damage(amount) if amount > 0 => commands.add(DamageAction {
+                entity: target,
+                amount,
+                source: Some(projectile.source),
+            }),
+            ProjectileHit::Damage(_) => {}
+        }
+        if let Some(status) = projectile.status {
+            commands.add(ApplyStatusAction {
+                entity: target,
+                status,
+                source: Some(projectile.source),
+            });
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use bevy::{ecs::system::Command, prelude::*};
+
+use crate::{events::StatusAppliedEvent, resources::RLTimeSystem, StatusEffects, StatusInflict};
+
+/// Inflicts a status on an entity, starting at the current game time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApplyStatusAction {
+    pub entity: Entity,
+    pub status: StatusInflict,
+    pub source: Option<Entity>,
+}
+
+impl Command for ApplyStatusAction {
+    fn apply(self, world: &mut World) {
+        let now = world.resource::<RLTimeSystem>().get_time();
+        let status = self.status;
+        match world.get_mut::<StatusEffects>(self.entity) {
+            Some(mut effects) => effects.apply(
+                status.kind,
+                status.duration,
+                status.magnitude,
+                self.source,
+                now,
+            ),
+            None => return,
+        }
+        info!("ApplyStatusAction: {:?} is {:?}", self.entity, status);
+        world.send_event(StatusAppliedEvent {
+            entity: self.entity,
+            kind: status.kind,
+        });
+    }
+}
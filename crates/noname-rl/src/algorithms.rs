@@ -64,7 +64,7 @@ pub fn bresenham_line(
         x += 1;
     }
     // println!("cells: {:?}", cells);
-    if cells[0].x != start_x as u32 && cells[0].y != start_y as u32 {
+    if cells[0] != TilePos::new(start_x as u32, start_y as u32) {
         // println!("cells[0] != x0 || cells[0] != y0");
         cells.reverse();
     }
@@ -97,6 +97,169 @@ pub fn tile_pos_to_world_pos(
     }
 }
 
+/// A* search on a 4-connected grid.
+///
+/// `cost` returns the cost of entering a tile or `None` if it can't be
+/// entered. The returned path starts at `start` and ends at `goal`.
+pub fn a_star(
+    start: TilePos,
+    goal: TilePos,
+    size: &TilemapSize,
+    cost: impl Fn(&TilePos) -> Option<u32>,
+) -> Option<Vec<TilePos>> {
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap};
+
+    let heuristic = |pos: &TilePos| pos.x.abs_diff(goal.x) + pos.y.abs_diff(goal.y);
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<TilePos, TilePos> = HashMap::new();
+    let mut best_cost: HashMap<TilePos, u32> = HashMap::new();
+
+    best_cost.insert(start, 0);
+    open.push(Reverse((heuristic(&start), 0, start.x, start.y)));
+
+    while let Some(Reverse((_, current_cost, x, y))) = open.pop() {
+        let current = TilePos::new(x, y);
+        if current == goal {
+            let mut path = vec![current];
+            let mut current = current;
+            while let Some(previous) = came_from.get(&current) {
+                path.push(*previous);
+                current = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        if best_cost.get(&current).map_or(false, |&c| c < current_cost) {
+            continue;
+        }
+
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            if nx < 0 || ny < 0 || nx >= size.x as i32 || ny >= size.y as i32 {
+                continue;
+            }
+            let next = TilePos::new(nx as u32, ny as u32);
+            let step_cost = match cost(&next) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+
+            let next_cost = current_cost + step_cost;
+            if best_cost.get(&next).map_or(true, |&c| next_cost < c) {
+                best_cost.insert(next, next_cost);
+                came_from.insert(next, current);
+                open.push(Reverse((
+                    next_cost + heuristic(&next),
+                    next_cost,
+                    next.x,
+                    next.y,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// Dijkstra map on a 4-connected grid.
+///
+/// Cheapest cost of reaching every tile from `start`, up to `max_cost`.
+/// `cost` works as for `a_star`.
+pub fn dijkstra_map(
+    start: TilePos,
+    size: &TilemapSize,
+    max_cost: u32,
+    cost: impl Fn(&TilePos) -> Option<u32>,
+) -> std::collections::HashMap<TilePos, u32> {
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap};
+
+    let mut open = BinaryHeap::new();
+    let mut best_cost: HashMap<TilePos, u32> = HashMap::new();
+
+    best_cost.insert(start, 0);
+    open.push(Reverse((0, start.x, start.y)));
+
+    while let Some(Reverse((current_cost, x, y))) = open.pop() {
+        let current = TilePos::new(x, y);
+        if best_cost.get(&current).map_or(false, |&c| c < current_cost) {
+            continue;
+        }
+
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            if nx < 0 || ny < 0 || nx >= size.x as i32 || ny >= size.y as i32 {
+                continue;
+            }
+            let next = TilePos::new(nx as u32, ny as u32);
+            let next_cost = match cost(&next) {
+                Some(step_cost) => current_cost + step_cost,
+                None => continue,
+            };
+            if next_cost > max_cost {
+                continue;
+            }
+            if best_cost.get(&next).map_or(true, |&c| next_cost < c) {
+                best_cost.insert(next, next_cost);
+                open.push(Reverse((next_cost, next.x, next.y)));
+            }
+        }
+    }
+
+    best_cost
+}
+
 pub mod prelude {
-    pub use super::bresenham_line;
+    pub use super::{a_star, bresenham_line, dijkstra_map};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: TilemapSize = TilemapSize { x: 5, y: 3 };
+
+    #[test]
+    fn a_star_unreachable_goal() {
+        // a wall across the whole map
+        let cost = |pos: &TilePos| (pos.x != 2).then_some(1);
+        let path = a_star(TilePos::new(0, 1), TilePos::new(4, 1), &SIZE, cost);
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn a_star_goes_around_costly_tiles() {
+        let cost = |pos: &TilePos| Some(if *pos == TilePos::new(2, 1) { 10 } else { 1 });
+        let path = a_star(TilePos::new(0, 1), TilePos::new(4, 1), &SIZE, cost).unwrap();
+        assert_eq!(path.first(), Some(&TilePos::new(0, 1)));
+        assert_eq!(path.last(), Some(&TilePos::new(4, 1)));
+        assert!(!path.contains(&TilePos::new(2, 1)));
+        // two steps aside are cheaper than the costly tile
+        assert_eq!(path.len(), 7);
+    }
+
+    #[test]
+    fn dijkstra_map_skips_unreachable_tiles() {
+        let cost = |pos: &TilePos| (pos.x != 2).then_some(1);
+        let distances = dijkstra_map(TilePos::new(0, 1), &SIZE, 100, cost);
+        assert_eq!(distances.len(), 6);
+        assert_eq!(distances.get(&TilePos::new(1, 2)), Some(&2));
+        assert!(!distances.contains_key(&TilePos::new(3, 1)));
+    }
+
+    #[test]
+    fn dijkstra_map_goes_around_costly_tiles() {
+        let cost = |pos: &TilePos| Some(if *pos == TilePos::new(2, 1) { 10 } else { 1 });
+        let distances = dijkstra_map(TilePos::new(0, 1), &SIZE, 100, cost);
+        assert_eq!(distances.get(&TilePos::new(2, 1)), Some(&11));
+        assert_eq!(distances.get(&TilePos::new(4, 1)), Some(&6));
+        // and stops at `max_cost`
+        let distances = dijkstra_map(TilePos::new(0, 1), &SIZE, 5, cost);
+        assert!(!distances.contains_key(&TilePos::new(4, 1)));
+    }
 }
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Attack, Defense, Health};
+
+/// MonsterDefinition
+///
+/// Static description of a monster kind, loaded from `assets/data/monsters.ron`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MonsterDefinition {
+    pub id: String,
+    pub name: String,
+    pub sprite: usize,
+    pub health: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub xp: u32,
+    #[serde(default)]
+    pub spawn_weight: u32,
+}
+
+#[derive(Debug, Clone, Resource, Deserialize)]
+pub struct MonsterTable {
+    monsters: Vec<MonsterDefinition>,
+}
+
+impl Default for MonsterTable {
+    fn default() -> Self {
+        ron::from_str(include_str!("../assets/data/monsters.ron")).expect("invalid monsters.ron")
+    }
+}
+
+impl MonsterTable {
+    pub fn get(&self, id: &str) -> Option<&MonsterDefinition> {
+        self.monsters.iter().find(|monster| monster.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MonsterDefinition> {
+        self.monsters.iter()
+    }
+}
+
+/// Id of the `MonsterDefinition` a monster was spawned from.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct MonsterKind {
+    pub id: String,
+}
+
+/// Experience and level of an actor. Levels gained but not spent on a
+/// stat yet are kept in `pending_level_ups`.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+    pub pending_level_ups: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0,
+            pending_level_ups: 0,
+        }
+    }
+}
+
+impl Experience {
+    /// total xp needed to reach `level`
+    pub fn threshold(level: u32) -> u32 {
+        10 * level.saturating_sub(1) * level
+    }
+
+    pub fn next_threshold(&self) -> u32 {
+        Self::threshold(self.level + 1)
+    }
+
+    /// adds xp, returning the number of levels gained
+    pub fn gain(&mut self, xp: u32) -> u32 {
+        self.xp += xp;
+        let mut gained = 0;
+        while self.xp >= self.next_threshold() {
+            self.level += 1;
+            self.pending_level_ups += 1;
+            gained += 1;
+        }
+        gained
+    }
+}
+
+/// Stat raised by the player on level-up.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelUpChoice {
+    MaxHealth,
+    Attack,
+    Defense,
+}
+
+impl LevelUpChoice {
+    pub const ALL: [LevelUpChoice; 3] = [
+        LevelUpChoice::MaxHealth,
+        LevelUpChoice::Attack,
+        LevelUpChoice::Defense,
+    ];
+
+    /// raises the chosen stat
+    pub fn apply(&self, health: &mut Health, attack: &mut Attack, defense: &mut Defense) {
+        match self {
+            LevelUpChoice::MaxHealth => {
+                health.max += 10;
+                health.current += 10;
+            }
+            LevelUpChoice::Attack => attack.damage += 1,
+            LevelUpChoice::Defense => defense.value += 1,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LevelUpChoice::MaxHealth => "+10 max health",
+            LevelUpChoice::Attack => "+1 attack",
+            LevelUpChoice::Defense => "+1 defense",
+        }
+    }
+}
+
+/// max health gained on every level-up, on top of the chosen stat
+pub const LEVEL_UP_HEALTH: i32 = 5;
+
+/// attack is raised by one every `LEVEL_UP_ATTACK_EVERY` levels
+pub const LEVEL_UP_ATTACK_EVERY: u32 = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_crosses_level_thresholds() {
+        assert_eq!(Experience::threshold(1), 0);
+        assert_eq!(Experience::threshold(2), 20);
+        assert_eq!(Experience::threshold(3), 60);
+
+        let mut experience = Experience::default();
+        assert_eq!(experience.gain(19), 0);
+        assert_eq!(experience.level, 1);
+        assert_eq!(experience.gain(1), 1);
+        assert_eq!(experience.level, 2);
+
+        // several levels at once are all left to choose
+        assert_eq!(experience.gain(100), 2);
+        assert_eq!((experience.level, experience.xp), (4, 120));
+        assert_eq!(experience.pending_level_ups, 3);
+    }
+}
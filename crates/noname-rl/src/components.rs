@@ -1,27 +1,62 @@
 use bevy::{prelude::*, reflect::Reflect};
 use bevy_ecs_tilemap::tiles::TilePos;
 use leafwing_input_manager::Actionlike;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Default, Debug)]
-pub struct WalkingAudioEffect {}
+use crate::{Equipment, Experience, Inventory, StatusEffects, VolumeChannel};
 
 // stats components
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
 }
 
-#[derive(Component, Default, Debug)]
+#[derive(Component, Default, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Attack {
     pub damage: i32,
 }
 
+/// reduces the damage taken from attacks
+#[derive(Component, Default, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Defense {
+    pub value: i32,
+}
+
+/// Stats after applying the equipment bonuses to the base `Health::max`,
+/// `Attack` and `Defense`. Kept up to date by `update_effective_stats`.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
+pub struct EffectiveStats {
+    pub max_health: i32,
+    pub attack: i32,
+    pub defense: i32,
+}
+
 #[derive(Bundle, Default, Debug)]
 pub struct StatsBundle {
     pub health: Health,
     pub attack: Attack,
+    pub defense: Defense,
+    pub effective: EffectiveStats,
+}
+
+impl StatsBundle {
+    pub fn new(health: i32, attack: i32, defense: i32) -> Self {
+        Self {
+            health: Health {
+                current: health,
+                max: health,
+            },
+            attack: Attack { damage: attack },
+            defense: Defense { value: defense },
+            effective: EffectiveStats {
+                max_health: health,
+                attack,
+                defense,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Component)]
@@ -37,6 +72,23 @@ pub enum RLAction {
     Down,
     Left,
     Right,
+    OpenDoor,
+    CloseDoor,
+    PickUp,
+    Drop,
+    NextItem,
+    UseItem,
+    Equip,
+    Unequip,
+    Fire,
+    NextTarget,
+    Confirm,
+    Cancel,
+    ToggleMinimap,
+    MapView,
+    Options,
+    Pause,
+    Undo,
 }
 
 #[derive(Component, Default)]
@@ -57,6 +109,11 @@ pub struct PlayerBundle {
     // pub input_manager: InputManagerBundle<RLAction>,
     pub visible_tiles: VisibleTiles,
     // pub visited_tiles: VisitedTiles,
+    pub inventory: Inventory,
+    pub equipment: Equipment,
+    pub can_open_doors: CanOpenDoors,
+    pub status_effects: StatusEffects,
+    pub experience: Experience,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Component)]
@@ -64,22 +121,45 @@ pub enum TileKind {
     #[default]
     Floor,
     Wall,
+    Door(DoorState),
+    Grass,
+    Water,
+    DeepWater,
+    Lava,
+    Rubble,
+    Chasm,
+    GlassWall,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    Closed,
+    /// closed and openable only by an actor carrying the matching key item
+    Locked {
+        key: u32,
+    },
+}
+
+/// Marks the actors that are able to open doors (by bumping or explicitly).
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq)]
+pub struct CanOpenDoors;
+
 #[derive(Component, Default)]
-pub struct FovOccluder;
+pub struct NeedsFovUpdate;
 
+/// text above a monster showing its state, see `DebugOverlays`
 #[derive(Component, Default)]
-pub struct Wall;
+pub struct AiStateLabel;
 
-#[derive(Bundle, Default)]
-pub struct WallBundle {
-    pub wall: Wall,
-    pub fov_occluder: FovOccluder,
-}
+/// takes no damage (god mode of the debug console)
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Invulnerable;
 
-#[derive(Component, Default)]
-pub struct NeedsFovUpdate;
+/// Belongs to the running game (map, actors, items, game UI): despawned
+/// by `teardown_game` when the game is left.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct GameEntity;
 
 // #[derive(Component, Default)]
 // pub struct TileBundle {
@@ -121,13 +201,79 @@ pub struct TimeUIField {}
 #[derive(Component, Default)]
 pub struct TileInfoUI {}
 
+#[derive(Component, Default)]
+pub struct TileTooltipUI {}
+
+#[derive(Component, Default)]
+pub struct InventoryUI {}
+
+#[derive(Component, Default)]
+pub struct CharacterUI {}
+
+#[derive(Component, Default)]
+pub struct LevelUpUI {}
+
+#[derive(Component, Default)]
+pub struct GameLogPanel {}
+
+#[derive(Component, Default)]
+pub struct GameLogUI {}
+
+#[derive(Component, Default)]
+pub struct OptionsUI {}
+
+/// button of the options menu changing a volume by `delta`
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct VolumeButton {
+    pub channel: VolumeChannel,
+    pub delta: f32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct VolumeLabel {
+    pub channel: VolumeChannel,
+}
+
+/// root of a menu screen (main menu, pause, game over), despawned when
+/// its state is left
+#[derive(Component, Default)]
+pub struct MenuScreen {}
+
+/// buttons of the menu screens, see `menu_button_interaction`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuButton {
+    NewGame,
+    Continue,
+    Resume,
+    SaveAndQuit,
+    MainMenu,
+    Quit,
+}
+
+#[derive(Component, Default)]
+pub struct SeedInputUI {}
+
+#[derive(Component, Default)]
+pub struct MinimapUI {}
+
+#[derive(Component, Default)]
+pub struct MinimapMarker {}
+
 #[derive(Component, Default)]
 pub struct Name {
     pub name: String,
 }
 
-#[derive(Component, Default)]
-pub struct PlayerPositionUILabel {}
+/// HUD elements, see `spawn_hud`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudElement {
+    HealthBar,
+    HealthText,
+    Stats,
+    Level,
+    DepthAndTurn,
+    Statuses,
+}
 
 #[derive(Component, Default)]
 pub struct TimeUIButton {}
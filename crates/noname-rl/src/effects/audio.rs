@@ -1,43 +1,176 @@
-use std::fmt::{Debug, Display, Formatter};
+use std::collections::HashMap;
 
-use bevy::{audio, ecs::system::Command, prelude::*};
+use bevy::{
+    audio::{PlaybackMode, Volume},
+    prelude::*,
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
 
-use crate::WalkingAudioEffect;
+use crate::{
+    events::{DamageEvent, DeathEvent, LevelUpEvent, SoundEvent},
+    AudioSettings, GameMap, Player,
+};
 
-#[derive(Component)]
-pub struct PlayAudioEffect<T: Component + Debug + Default> {
-    pub effect: T,
+/// distance (in tiles) at which a sound can't be heard anymore
+pub const HEARING_RANGE: f32 = 16.;
+
+/// What a sound effect is played for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum SoundKind {
+    Step,
+    BumpWall,
+    Hit,
+    Miss,
+    Death,
+    PickUp,
+    Door,
+    LevelUp,
 }
 
-impl<T: Component + Debug + Default> Command for PlayAudioEffect<T> {
-    fn apply(self, world: &mut World) {
-        let mut audio_effect =
-            world.query_filtered::<&Handle<AudioSource>, With<WalkingAudioEffect>>();
-        info!("PlayAudioEffect: {:?}", self.effect);
-        if let Ok(audio_effect) = audio_effect.get_single(world) {
-            // audio_effect.
-            world.spawn(AudioBundle {
-                source: audio_effect.clone(),
-                settings: PlaybackSettings::DESPAWN,
-            });
-        }
-    }
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SoundDefinition {
+    /// asset paths, one is picked at random each time
+    pub paths: Vec<String>,
+    pub volume: f32,
+    #[serde(default)]
+    pub volume_variation: f32,
+    /// variation of the playback speed, which changes the pitch
+    #[serde(default)]
+    pub pitch_variation: f32,
 }
 
-impl<T: Component + Debug + Default> Default for PlayAudioEffect<T> {
-    fn default() -> Self {
-        Self {
-            effect: T::default(),
-        }
+#[derive(Debug, Clone, Deserialize)]
+struct SoundTable {
+    sounds: HashMap<SoundKind, SoundDefinition>,
+}
+
+/// SoundBank
+///
+/// Sound effects of `assets/data/sounds.ron` with their loaded handles.
+#[derive(Resource, Debug, Default)]
+pub struct SoundBank {
+    sounds: HashMap<SoundKind, (SoundDefinition, Vec<Handle<AudioSource>>)>,
+}
+
+impl SoundBank {
+    /// a random variant of the sound with its playback settings
+    pub fn pick(&self, kind: SoundKind) -> Option<(Handle<AudioSource>, PlaybackSettings)> {
+        let (definition, handles) = self.sounds.get(&kind)?;
+        let mut rng = rand::thread_rng();
+        let handle = handles.choose(&mut rng)?.clone();
+
+        let vary = |rng: &mut rand::rngs::ThreadRng, amount: f32| {
+            if amount > 0. {
+                rng.gen_range(-amount..=amount)
+            } else {
+                0.
+            }
+        };
+        let volume = (definition.volume + vary(&mut rng, definition.volume_variation)).max(0.);
+        let speed = (1. + vary(&mut rng, definition.pitch_variation)).max(0.1);
+        let settings = PlaybackSettings {
+            mode: PlaybackMode::Despawn,
+            volume: Volume::new_relative(volume),
+            speed,
+            paused: false,
+        };
+        Some((handle, settings))
     }
 }
 
-impl<T: Component + Debug + Default> Display for PlayAudioEffect<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "PlayAudioEffect: {:?}", self.effect)
+pub fn sound_bank_setup(assets: Res<AssetServer>, mut commands: Commands) {
+    let table: SoundTable =
+        ron::from_str(include_str!("../../assets/data/sounds.ron")).expect("invalid sounds.ron");
+    let sounds = table
+        .sounds
+        .into_iter()
+        .map(|(kind, definition)| {
+            let handles = definition
+                .paths
+                .iter()
+                .map(|path| assets.load(path.as_str()))
+                .collect();
+            (kind, (definition, handles))
+        })
+        .collect();
+    commands.insert_resource(SoundBank { sounds });
+}
+
+/// Turns combat and progression events into sounds, while dead entities
+/// still have their position.
+pub fn gameplay_sounds(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut level_up_events: EventReader<LevelUpEvent>,
+    positions_q: Query<&TilePos>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    for event in damage_events.iter() {
+        let kind = if event.amount > 0 {
+            SoundKind::Hit
+        } else {
+            SoundKind::Miss
+        };
+        sound_events.send(SoundEvent {
+            kind,
+            position: positions_q.get(event.target).ok().copied(),
+        });
+    }
+    for event in death_events.iter() {
+        sound_events.send(SoundEvent {
+            kind: SoundKind::Death,
+            position: positions_q.get(event.entity).ok().copied(),
+        });
+    }
+    for _ in level_up_events.iter() {
+        sound_events.send(SoundEvent {
+            kind: SoundKind::LevelUp,
+            position: None,
+        });
     }
 }
 
-// pub fn audio_effects_system(assets: Res<MyAssets>,walking_effect: Query<&AudioSink, With<WalkingAudioEffect>>) {
+/// Plays the sound events, quieter the further they are from the player.
+/// What happens outside of the player's field of view makes no sound.
+pub fn play_sounds(
+    mut sound_events: EventReader<SoundEvent>,
+    settings: Res<AudioSettings>,
+    bank: Option<Res<SoundBank>>,
+    map: Option<Res<GameMap>>,
+    player_q: Query<&TilePos, With<Player>>,
+    mut commands: Commands,
+) {
+    let (bank, map) = match (bank, map) {
+        (Some(bank), Some(map)) => (bank, map),
+        _ => return,
+    };
+    let player_pos = player_q.get_single().ok();
 
-// }
+    for event in sound_events.iter() {
+        let attenuation = match (event.position, player_pos) {
+            (Some(pos), Some(player_pos)) => {
+                if !map.is_visible(&pos) {
+                    continue;
+                }
+                let distance = Vec2::new(pos.x as f32, pos.y as f32)
+                    .distance(Vec2::new(player_pos.x as f32, player_pos.y as f32));
+                1. - (distance / HEARING_RANGE).min(1.)
+            }
+            _ => 1.,
+        } * settings.sfx_volume();
+        if attenuation <= 0. {
+            continue;
+        }
+
+        let (source, mut settings) = match bank.pick(event.kind) {
+            Some(sound) => sound,
+            None => continue,
+        };
+        if let Volume::Relative(volume) = settings.volume {
+            settings.volume = Volume::new_relative(volume.get() * attenuation);
+        }
+        commands.spawn(AudioBundle { source, settings });
+    }
+}
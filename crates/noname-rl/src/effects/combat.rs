@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_tweening::{
+    lens::{TextColorLens, TransformPositionLens},
+    Animator, EaseFunction, Lens, Sequence, Tween, TweenCompleted,
+};
+
+use crate::{
+    events::{DamageEvent, DeathEvent, ScreenShakeEvent},
+    GameEntity, GameMap, Health, MyAssets, Player,
+};
+
+/// `TweenCompleted::user_data` of the tweens whose entity is despawned when
+/// they end (see `despawn_finished_effects`)
+pub const DESPAWN_ON_COMPLETION: u64 = 0xDEAD;
+
+/// Tints a `TextureAtlasSprite`, the sprite kind used by actors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteSheetColorLens {
+    pub start: Color,
+    pub end: Color,
+}
+
+impl Lens<TextureAtlasSprite> for SpriteSheetColorLens {
+    fn lerp(&mut self, target: &mut TextureAtlasSprite, ratio: f32) {
+        let start: Vec4 = self.start.into();
+        let end: Vec4 = self.end.into();
+        target.color = start.lerp(end, ratio).into();
+    }
+}
+
+/// Damage numbers rising from the hit entity, plus a red flash and a short
+/// shake of the target. The screen shakes when the player is hurt.
+pub fn spawn_combat_feedback(
+    mut damage_events: EventReader<DamageEvent>,
+    targets_q: Query<(&Transform, &TilePos, &Health, Option<&Player>), With<TextureAtlasSprite>>,
+    map: Res<GameMap>,
+    assets: Res<MyAssets>,
+    mut shake_events: EventWriter<ScreenShakeEvent>,
+    mut commands: Commands,
+) {
+    for event in damage_events.iter() {
+        let (transform, tile_pos, health, player) = match targets_q.get(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if player.is_some() && event.amount > 0 {
+            let share = event.amount as f32 / health.max.max(1) as f32;
+            shake_events.send(ScreenShakeEvent {
+                trauma: 0.2 + share,
+            });
+        }
+        let start = transform.translation.truncate().extend(20.);
+
+        let rise = Tween::new(
+            EaseFunction::QuadraticOut,
+            Duration::from_millis(800),
+            TransformPositionLens {
+                start,
+                end: start + Vec3::new(0., 24., 0.),
+            },
+        )
+        .with_completed_event(DESPAWN_ON_COMPLETION);
+        let fade = Tween::new(
+            EaseFunction::QuadraticIn,
+            Duration::from_millis(800),
+            TextColorLens {
+                start: Color::RED,
+                end: Color::rgba(1., 0., 0., 0.),
+                section: 0,
+            },
+        );
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("-{}", event.amount),
+                    TextStyle {
+                        font: assets.ui_font.clone(),
+                        font_size: 18.0,
+                        color: Color::RED,
+                    },
+                ),
+                transform: Transform::from_translation(start).with_scale(Vec3::splat(0.5)),
+                ..Default::default()
+            },
+            Name::new("DamageNumber"),
+            Animator::new(rise),
+            Animator::new(fade),
+            GameEntity,
+        ));
+
+        // the dead are despawned this frame and faded out by `spawn_death_fades`
+        if health.current <= 0 {
+            continue;
+        }
+
+        let flash = Tween::new(
+            EaseFunction::QuadraticOut,
+            Duration::from_millis(200),
+            SpriteSheetColorLens {
+                start: Color::RED,
+                end: Color::WHITE,
+            },
+        );
+        // the shake ends on the logical tile, so interrupting a move tween
+        // still leaves the sprite where the entity is
+        let rest = map.tile_to_world(tile_pos).extend(transform.translation.z);
+        let offset = Vec3::new(2., 0., 0.);
+        let shake = [rest + offset, rest - offset, rest]
+            .into_iter()
+            .scan(transform.translation, |from, to| {
+                let tween = Tween::new(
+                    EaseFunction::SineInOut,
+                    Duration::from_millis(40),
+                    TransformPositionLens {
+                        start: *from,
+                        end: to,
+                    },
+                );
+                *from = to;
+                Some(tween)
+            })
+            .collect::<Vec<_>>();
+
+        commands
+            .entity(event.target)
+            .insert((Animator::new(flash), Animator::new(Sequence::new(shake))));
+    }
+}
+
+/// Fades out a copy of the sprite of dying entities: the entity itself is
+/// despawned right away so the game logic never waits for the animation.
+pub fn spawn_death_fades(
+    mut death_events: EventReader<DeathEvent>,
+    sprites_q: Query<(&Transform, &TextureAtlasSprite, &Handle<TextureAtlas>)>,
+    mut commands: Commands,
+) {
+    for event in death_events.iter() {
+        let (transform, sprite, atlas) = match sprites_q.get(event.entity) {
+            Ok(sprite) => sprite,
+            Err(_) => continue,
+        };
+
+        let fade = Tween::new(
+            EaseFunction::QuadraticIn,
+            Duration::from_millis(600),
+            SpriteSheetColorLens {
+                start: Color::WHITE,
+                end: Color::rgba(1., 1., 1., 0.),
+            },
+        )
+        .with_completed_event(DESPAWN_ON_COMPLETION);
+
+        commands.spawn((
+            SpriteSheetBundle {
+                texture_atlas: atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    color: Color::WHITE,
+                    ..sprite.clone()
+                },
+                transform: *transform,
+                ..Default::default()
+            },
+            Name::new("DeathFade"),
+            Animator::new(fade),
+            GameEntity,
+        ));
+    }
+}
+
+pub fn despawn_finished_effects(mut reader: EventReader<TweenCompleted>, mut commands: Commands) {
+    for event in reader.iter() {
+        if event.user_data == DESPAWN_ON_COMPLETION {
+            if let Some(mut entity) = commands.get_entity(event.entity) {
+                entity.despawn();
+            }
+        }
+    }
+}
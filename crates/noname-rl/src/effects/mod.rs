@@ -1,5 +1,9 @@
 mod audio;
+mod combat;
+mod music;
 
 pub mod prelude {
     pub use super::audio::*;
+    pub use super::combat::*;
+    pub use super::music::*;
 }
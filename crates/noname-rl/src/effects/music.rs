@@ -0,0 +1,132 @@
+use bevy::{
+    audio::{AudioSinkPlayback, Volume},
+    prelude::*,
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::Deserialize;
+
+use crate::{resources::Dungeon, AudioSettings, GameMap, Monster};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AmbienceTrack {
+    pub from_depth: u32,
+    pub path: String,
+}
+
+/// MusicTable
+///
+/// Music tracks of `assets/data/music.ron`.
+#[derive(Resource, Debug, Clone, PartialEq, Deserialize)]
+pub struct MusicTable {
+    pub ambience: Vec<AmbienceTrack>,
+    pub combat: String,
+    pub crossfade_secs: f32,
+    pub combat_cooldown_secs: f32,
+}
+
+impl Default for MusicTable {
+    fn default() -> Self {
+        ron::from_str(include_str!("../../assets/data/music.ron")).expect("invalid music.ron")
+    }
+}
+
+impl MusicTable {
+    pub fn ambience_for(&self, depth: u32) -> Option<&str> {
+        self.ambience
+            .iter()
+            .filter(|track| track.from_depth <= depth)
+            .max_by_key(|track| track.from_depth)
+            .map(|track| track.path.as_str())
+    }
+}
+
+/// A playing music track. Tracks fade in when they start and fade out
+/// before being despawned, see `crossfade_music`.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct MusicTrack {
+    pub path: String,
+    /// 0 is silent, 1 is the music volume of the settings
+    pub fade: f32,
+    pub fading_out: bool,
+}
+
+/// Picks the track for the situation: combat music while monsters are in
+/// sight (and a little after), the ambience of the depth otherwise.
+pub fn update_music(
+    table: Res<MusicTable>,
+    dungeon: Res<Dungeon>,
+    map: Res<GameMap>,
+    monsters_q: Query<&TilePos, With<Monster>>,
+    mut tracks_q: Query<&mut MusicTrack>,
+    assets: Res<AssetServer>,
+    time: Res<Time>,
+    mut calm_secs: Local<f32>,
+    mut commands: Commands,
+) {
+    if monsters_q.iter().any(|pos| map.is_visible(pos)) {
+        *calm_secs = 0.;
+    } else {
+        *calm_secs += time.delta_seconds();
+    }
+    let desired = if *calm_secs < table.combat_cooldown_secs {
+        Some(table.combat.as_str())
+    } else {
+        table.ambience_for(dungeon.depth)
+    };
+
+    let playing = tracks_q
+        .iter()
+        .find(|track| !track.fading_out)
+        .map(|track| track.path.clone());
+    if playing.as_deref() == desired {
+        return;
+    }
+
+    info!("music: {:?} -> {:?}", playing, desired);
+    let mut resumed = false;
+    for mut track in tracks_q.iter_mut() {
+        // a track still fading out comes back instead of starting over
+        track.fading_out = Some(track.path.as_str()) != desired || resumed;
+        resumed |= !track.fading_out;
+    }
+    if let (Some(path), false) = (desired, resumed) {
+        commands.spawn((
+            AudioBundle {
+                source: assets.load(path),
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.)),
+            },
+            MusicTrack {
+                path: path.to_string(),
+                fade: 0.,
+                fading_out: false,
+            },
+            Name::new("MusicTrack"),
+        ));
+    }
+}
+
+/// Fades the tracks in and out and applies the music volume.
+pub fn crossfade_music(
+    table: Res<MusicTable>,
+    settings: Res<AudioSettings>,
+    time: Res<Time>,
+    mut tracks_q: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+    mut commands: Commands,
+) {
+    let step = time.delta_seconds() / table.crossfade_secs.max(0.01);
+    for (entity, mut track, sink) in tracks_q.iter_mut() {
+        if track.fading_out {
+            track.fade = (track.fade - step).max(0.);
+            if track.fade == 0. {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        } else {
+            track.fade = (track.fade + step).min(1.);
+        }
+        // the sink only exists once the track started playing
+        if let Some(sink) = sink {
+            sink.set_volume(track.fade * settings.music_volume());
+        }
+    }
+}
@@ -1,13 +1,54 @@
-use bevy::prelude::Event;
+use bevy::prelude::{Entity, Event};
 use bevy_ecs_tilemap::tiles::TilePos;
 
+use crate::{effects::prelude::SoundKind, StatusKind};
+
 #[derive(Event, Debug, Clone, Copy)]
 pub struct TurnEndEvent;
 
 #[derive(Event, Debug, Clone, Copy, Default)]
 pub struct IntentionEndEvent;
 
+/// Plays a sound effect, heard from `position` if any (see `play_sounds`).
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SoundEvent {
+    pub kind: SoundKind,
+    pub position: Option<TilePos>,
+}
+
+/// Shakes the game camera; `trauma` adds up and is capped at 1.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScreenShakeEvent {
+    pub trauma: f32,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: i32,
+    pub source: Option<Entity>,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct StatusAppliedEvent {
+    pub entity: Entity,
+    pub kind: StatusKind,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct StatusExpiredEvent {
+    pub entity: Entity,
+    pub kind: StatusKind,
+}
+
 #[derive(Event, Debug, Clone, Copy)]
-pub struct TileInfoEvent {
-    pub tile_pos: TilePos,
+pub struct LevelUpEvent {
+    pub entity: Entity,
+    pub level: u32,
 }
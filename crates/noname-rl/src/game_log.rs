@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::Command, prelude::*};
+
+use crate::{resources::RLTimeSystem, Player};
+
+/// entries kept by a `GameLog`, older ones are dropped
+pub const GAME_LOG_CAPACITY: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogCategory {
+    Info,
+    Combat,
+    Damage,
+    Death,
+    Item,
+    Blocked,
+    Status,
+    Progress,
+}
+
+impl LogCategory {
+    pub fn color(&self) -> Color {
+        match self {
+            LogCategory::Info => Color::hex("f7d8bc").unwrap(),
+            LogCategory::Combat => Color::hex("e8a05a").unwrap(),
+            LogCategory::Damage => Color::hex("e05050").unwrap(),
+            LogCategory::Death => Color::hex("b03030").unwrap(),
+            LogCategory::Item => Color::hex("f0d060").unwrap(),
+            LogCategory::Blocked => Color::hex("909090").unwrap(),
+            LogCategory::Status => Color::hex("a070e0").unwrap(),
+            LogCategory::Progress => Color::hex("70d070").unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    /// `RLTimeSystem` time the entry was written at
+    pub turn: u32,
+    pub category: LogCategory,
+    pub text: String,
+}
+
+/// GameLog
+///
+/// Messages shown to the player in the log panel, oldest first.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct GameLog {
+    pub entries: VecDeque<LogEntry>,
+    /// number of entries hidden below the bottom of the panel
+    pub scroll: usize,
+}
+
+impl GameLog {
+    pub fn push(&mut self, turn: u32, category: LogCategory, text: impl Into<String>) {
+        let text = text.into();
+        info!("[{}] {}", turn, text);
+        self.entries.push_back(LogEntry {
+            turn,
+            category,
+            text,
+        });
+        if self.entries.len() > GAME_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        // a scrolled log stays on the same entries
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.entries.len().saturating_sub(1));
+        }
+    }
+
+    /// scrolls up (towards older entries) by `lines`, negative to scroll down
+    pub fn scroll_by(&mut self, lines: i32) {
+        let max = self.entries.len().saturating_sub(1) as i32;
+        self.scroll = (self.scroll as i32 + lines).clamp(0, max) as usize;
+    }
+
+    /// the `count` entries visible at the current scroll position
+    pub fn visible(&self, count: usize) -> impl Iterator<Item = &LogEntry> {
+        let end = self.entries.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(count);
+        self.entries.range(start..end)
+    }
+}
+
+/// Writes a message to the `GameLog`, stamped with the current game time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogMessage {
+    pub category: LogCategory,
+    pub text: String,
+}
+
+impl LogMessage {
+    pub fn new(category: LogCategory, text: impl Into<String>) -> Self {
+        Self {
+            category,
+            text: text.into(),
+        }
+    }
+}
+
+impl Command for LogMessage {
+    fn apply(self, world: &mut World) {
+        let turn = world.resource::<RLTimeSystem>().get_time();
+        world
+            .resource_mut::<GameLog>()
+            .push(turn, self.category, self.text);
+    }
+}
+
+/// How an entity is called in the log: "you" for the player.
+pub fn describe(name: Option<&Name>, is_player: bool) -> String {
+    match name {
+        _ if is_player => "you".to_string(),
+        Some(name) => format!("the {}", name.as_str()),
+        None => "something".to_string(),
+    }
+}
+
+pub fn display_name(world: &World, entity: Entity) -> String {
+    match world.get_entity(entity) {
+        Some(entity) => describe(entity.get::<Name>(), entity.contains::<Player>()),
+        None => "something".to_string(),
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    CanOpenDoors, DoorAction, DoorState, GameMap, Inventory, ItemTable, LogCategory, LogMessage,
+};
+
+use super::{IntentionBundle, IntentionResolver, IntentionSourceRef};
+
+/// DoorIntention
+///
+/// An actor wants to open (`open: true`) or close the door at `target`.
+/// Bumping into a closed door turns a `MoveIntention` into one of these.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct DoorIntention {
+    pub target: TilePos,
+    pub source: IntentionSourceRef,
+    pub open: bool,
+}
+
+impl IntentionResolver for DoorIntention {
+    fn resolve_intention(
+        &self,
+        e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("DoorIntention: {:?}", self);
+        if world.get::<CanOpenDoors>(self.source.0).is_none() {
+            info!("{:?} can't use doors", self.source.0);
+            return None;
+        }
+
+        let state = match world.resource::<GameMap>().door(&self.target) {
+            Some(state) => state,
+            None => {
+                info!("no door at {:?}", self.target);
+                return None;
+            }
+        };
+
+        let new_state = match (state, self.open) {
+            (DoorState::Closed, true) => DoorState::Open,
+            (DoorState::Locked { key }, true) => {
+                let has_key = world
+                    .get::<Inventory>(self.source.0)
+                    .map_or(false, |inventory| {
+                        inventory.has_key(key, world.resource::<ItemTable>())
+                    });
+                if !has_key {
+                    info!("door at {:?} is locked", self.target);
+                    commands.add(LogMessage::new(LogCategory::Blocked, "the door is locked"));
+                    return None;
+                }
+                DoorState::Open
+            }
+            (DoorState::Open, false) => DoorState::Closed,
+            _ => return None,
+        };
+
+        commands.add(DoorAction {
+            entity: self.source.0,
+            target_tile: self.target,
+            state: new_state,
+        });
+        None
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    ApplyStatusAction, ConsumeItemAction, DropAction, EquipAction, Equipment, ExplodeAction,
+    GameMap, HealAction, Inventory, Item, ItemEffect, ItemTable, LogCategory, LogMessage,
+    PickUpAction, ProjectileAction, ProjectileHit, RevealMapAction, TeleportAction, UnequipAction,
+};
+
+use super::{line_of_fire, IntentionBundle, IntentionResolver, IntentionSourceRef};
+
+/// PickUpIntention
+///
+/// An actor wants to pick up the item entity `item` lying on its tile.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct PickUpIntention {
+    pub item: Entity,
+    pub source: IntentionSourceRef,
+}
+
+impl IntentionResolver for PickUpIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("PickUpIntention: {:?}", self);
+        let source = world.get_entity(self.source.0)?;
+        let item = world.get_entity(self.item)?;
+
+        if !item.contains::<Item>() || item.get::<TilePos>() != source.get::<TilePos>() {
+            info!("item {:?} is out of reach", self.item);
+            return None;
+        }
+
+        let inventory = source.get::<Inventory>()?;
+        let definition = world.resource::<ItemTable>().get(&item.get::<Item>()?.id)?;
+        if !inventory.has_room(definition) {
+            info!("inventory of {:?} is full", self.source.0);
+            commands.add(LogMessage::new(
+                LogCategory::Blocked,
+                "there is no room in the inventory",
+            ));
+            return None;
+        }
+
+        commands.add(PickUpAction {
+            entity: self.source.0,
+            item: self.item,
+        });
+        None
+    }
+}
+
+/// DropIntention
+///
+/// An actor wants to drop the inventory stack in `slot`.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct DropIntention {
+    pub slot: usize,
+    pub source: IntentionSourceRef,
+}
+
+impl IntentionResolver for DropIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("DropIntention: {:?}", self);
+        let inventory = world.get::<Inventory>(self.source.0)?;
+        if self.slot >= inventory.stacks.len() {
+            info!("nothing to drop in slot {}", self.slot);
+            return None;
+        }
+
+        commands.add(DropAction {
+            entity: self.source.0,
+            slot: self.slot,
+        });
+        None
+    }
+}
+
+/// UseItemIntention
+///
+/// An actor wants to use the item in inventory `slot`. Items whose effect
+/// needs a target (see `ItemEffect::target_range`) must provide `target`.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct UseItemIntention {
+    pub slot: usize,
+    pub source: IntentionSourceRef,
+    pub target: Option<TilePos>,
+}
+
+impl IntentionResolver for UseItemIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("UseItemIntention: {:?}", self);
+        let source = self.source.0;
+        let stack = world.get::<Inventory>(source)?.stacks.get(self.slot)?;
+        let definition = world.resource::<ItemTable>().get(&stack.id)?;
+        let effect = match definition.use_effect {
+            Some(effect) => effect,
+            None => {
+                info!("{} can't be used", definition.name);
+                return None;
+            }
+        };
+
+        if let Some(range) = effect.target_range() {
+            let target = self.target?;
+            let source_pos = world.get::<TilePos>(source)?;
+            let distance = source_pos
+                .x
+                .abs_diff(target.x)
+                .max(source_pos.y.abs_diff(target.y));
+            if distance > range || !world.resource::<GameMap>().is_visible(&target) {
+                info!("{:?} is out of range", target);
+                commands.add(LogMessage::new(LogCategory::Blocked, "out of range"));
+                return None;
+            }
+        }
+
+        commands.add(ConsumeItemAction {
+            entity: source,
+            slot: self.slot,
+        });
+        match effect {
+            ItemEffect::Heal(amount) => commands.add(HealAction {
+                entity: source,
+                amount,
+            }),
+            ItemEffect::Teleport => commands.add(TeleportAction {
+                entity: source,
+                target: None,
+            }),
+            ItemEffect::RevealMap => commands.add(RevealMapAction),
+            ItemEffect::Explode { damage, radius, .. } => commands.add(ExplodeAction {
+                center: self.target?,
+                radius,
+                damage,
+                source: Some(source),
+            }),
+            ItemEffect::Bolt { damage, status, .. } => {
+                let (path, target) = line_of_fire(world, source, self.target?)?;
+                commands.add(ProjectileAction {
+                    source,
+                    path,
+                    target,
+                    hit: ProjectileHit::Damage(damage),
+                    status,
+                });
+            }
+            ItemEffect::ApplyStatus(status) => commands.add(ApplyStatusAction {
+                entity: source,
+                status,
+                source: Some(source),
+            }),
+        }
+        None
+    }
+}
+
+/// EquipIntention
+///
+/// An actor wants to wear the item in inventory `slot`.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct EquipIntention {
+    pub slot: usize,
+    pub source: IntentionSourceRef,
+}
+
+impl IntentionResolver for EquipIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("EquipIntention: {:?}", self);
+        let source = world.get_entity(self.source.0)?;
+        if !source.contains::<Equipment>() {
+            return None;
+        }
+        let stack = source.get::<Inventory>()?.stacks.get(self.slot)?;
+        let definition = world.resource::<ItemTable>().get(&stack.id)?;
+        if definition.equip.is_none() {
+            info!("{} can't be equipped", definition.name);
+            return None;
+        }
+
+        commands.add(EquipAction {
+            entity: self.source.0,
+            slot: self.slot,
+        });
+        None
+    }
+}
+
+/// UnequipIntention
+///
+/// An actor wants to take off the item at `index` of its `Equipment`.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct UnequipIntention {
+    pub index: usize,
+    pub source: IntentionSourceRef,
+}
+
+impl IntentionResolver for UnequipIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("UnequipIntention: {:?}", self);
+        let equipment = world.get::<Equipment>(self.source.0)?;
+        if self.index >= equipment.items.len() {
+            info!("nothing equipped at {}", self.index);
+            return None;
+        }
+
+        commands.add(UnequipAction {
+            entity: self.source.0,
+            index: self.index,
+        });
+        None
+    }
+}
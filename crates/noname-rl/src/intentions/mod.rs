@@ -1,17 +1,35 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
 
-use crate::{components::EntityRef, MoveAction};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    components::EntityRef,
+    effects::prelude::SoundKind,
+    events::SoundEvent,
+    resources::{GameRng, RLTimeSystem},
+    AttackAction, GameMap, Health, LogCategory, LogMessage, Monster, MoveAction, Player,
+    StatusEffects, StatusKind,
+};
 
 #[derive(Debug, Clone, PartialEq, Component)]
 pub struct IntentionSourceRef(pub Entity);
 
+mod door_intention;
+mod item_intention;
 mod move_intention;
+mod ranged_intention;
+pub use door_intention::*;
+pub use item_intention::*;
 pub use move_intention::*;
+pub use ranged_intention::*;
 
 pub mod prelude {
     use super::*;
+    use door_intention::*;
+    use item_intention::*;
     use move_intention::*;
+    use ranged_intention::*;
 }
 
 /// IntentionResolver
@@ -26,6 +44,51 @@ pub trait IntentionResolver {
     ) -> Option<IntentionBundle>;
 }
 
+fn has_status(world: &World, entity: Entity, kind: StatusKind) -> bool {
+    world
+        .get::<StatusEffects>(entity)
+        .map_or(false, |effects| effects.has(kind))
+}
+
+/// A confused actor moves in a random direction half of the time.
+fn confused_move(intention: &MoveIntention, map: &GameMap, world: &World) -> Option<MoveIntention> {
+    let source = intention.source.0;
+    if !has_status(world, source, StatusKind::Confused) {
+        return None;
+    }
+    let source_pos = world.get::<TilePos>(source)?;
+    // rolled from the turn and position, so that replays stumble the same way
+    let turn = world.resource::<RLTimeSystem>().get_time() as u64;
+    let salt = turn << 32 | (source_pos.x as u64) << 16 | source_pos.y as u64;
+    let mut rng = world.resource::<GameRng>().derive(salt);
+    if rng.gen_bool(0.5) {
+        return None;
+    }
+
+    let cell = IVec2::new(source_pos.x as i32, source_pos.y as i32);
+    // actors are never stumbled into
+    let is_occupied = |pos: &TilePos| {
+        world
+            .iter_entities()
+            .any(|e| e.contains::<Health>() && e.get::<TilePos>() == Some(pos))
+    };
+    let directions = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+        .into_iter()
+        .filter_map(|d| map.checked_pos(cell + d))
+        .filter(|pos| !is_occupied(pos))
+        .collect::<Vec<_>>();
+    let target = *directions.choose(&mut rng)?;
+    info!("{:?} is confused and stumbles to {:?}", source, target);
+
+    Some(MoveIntention {
+        target,
+        source: intention.source.clone(),
+        target_position: map
+            .tile_to_world(&target)
+            .extend(intention.target_position.z),
+    })
+}
+
 #[derive(Debug, Clone, PartialEq, Component)]
 pub struct AttackIntention {
     pub target: IntentionSourceRef,
@@ -35,40 +98,82 @@ pub struct AttackIntention {
 
 pub fn process_attack_intention(
     entities_q: Query<(Entity, &AttackIntention)>,
+    ranged_q: Query<(Entity, &RangedAttackIntention)>,
     mut commands: Commands,
     world: &World,
 ) {
     for (entity, intention) in entities_q.iter() {
         let source_entity = intention.source.0;
         let target_entity = intention.target.0;
+        if has_status(world, source_entity, StatusKind::Stunned) {
+            info!("{:?} is stunned", source_entity);
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
         info!(
             "process_attack_intention [{:?}]: {:?} attacks {:?} at {:?}",
             intention, source_entity, target_entity, intention.target_pos
         );
+        // the target may have moved or died since the intention was created
+        let target_pos = world.get::<TilePos>(target_entity);
+        if target_pos == Some(&intention.target_pos) {
+            commands.add(AttackAction {
+                attacker: source_entity,
+                target: target_entity,
+            });
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (entity, intention) in ranged_q.iter() {
+        if has_status(world, intention.source.0, StatusKind::Stunned) {
+            info!("{:?} is stunned", intention.source.0);
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        intention.resolve_intention(entity, &mut commands, world);
         commands.entity(entity).despawn_recursive();
     }
 }
 
 pub fn process_move_intention(
     entities_q: Query<(Entity, &MoveIntention)>,
-    walls_q: Query<&TilePos, With<crate::components::Wall>>,
+    map: Res<GameMap>,
     mut commands: Commands,
     world: &World,
 ) {
     for (entity, intention) in entities_q.iter() {
-        //check if there is a wall
-        let mut is_blocked = false;
-        for wall_pos in walls_q.iter() {
-            if wall_pos == &intention.target {
-                is_blocked = true;
-                //continue 'outer;
-                break;
-            }
+        if has_status(world, intention.source.0, StatusKind::Stunned) {
+            info!("{:?} is stunned", intention.source.0);
+            commands.entity(entity).despawn_recursive();
+            continue;
         }
+        let confused = confused_move(intention, &map, world);
+        let intention = confused.as_ref().unwrap_or(intention);
+
+        //check if there is a wall
+        let is_blocked = map.is_blocked(&intention.target);
 
         // commands.despawn(entity);
-        if is_blocked {
+        if is_blocked && map.door(&intention.target).is_some() {
+            // bumping into a closed door tries to open it
+            commands.spawn(DoorIntention {
+                target: intention.target,
+                source: intention.source.clone(),
+                open: true,
+            });
+        } else if is_blocked {
             info!("tile {:?} is not accessible", intention.target);
+            if world.get::<Player>(intention.source.0).is_some() {
+                commands.add(LogMessage::new(LogCategory::Blocked, "the way is blocked"));
+                let position = Some(intention.target);
+                commands.add(move |world: &mut World| {
+                    world.send_event(SoundEvent {
+                        kind: SoundKind::BumpWall,
+                        position,
+                    });
+                });
+            }
         } else {
             println!(
                 "process_move_intention: {:?} for entity {:?}",
@@ -79,3 +184,61 @@ pub fn process_move_intention(
         commands.entity(entity).despawn_recursive();
     }
 }
+
+pub fn process_door_intention(
+    entities_q: Query<(Entity, &DoorIntention)>,
+    actors_q: Query<&TilePos, Or<(With<Player>, With<Monster>)>>,
+    mut commands: Commands,
+    world: &World,
+) {
+    for (entity, intention) in entities_q.iter() {
+        // a door can't be closed on someone standing in the doorway
+        let is_occupied = actors_q.iter().any(|pos| pos == &intention.target);
+
+        if !intention.open && is_occupied {
+            info!("door at {:?} is blocked", intention.target);
+            commands.add(LogMessage::new(
+                LogCategory::Blocked,
+                "something is in the doorway",
+            ));
+        } else {
+            intention.resolve_intention(entity, &mut commands, world);
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn process_item_intention(
+    pick_up_q: Query<(Entity, &PickUpIntention)>,
+    drop_q: Query<(Entity, &DropIntention)>,
+    use_q: Query<(Entity, &UseItemIntention)>,
+    equip_q: Query<(Entity, &EquipIntention)>,
+    unequip_q: Query<(Entity, &UnequipIntention)>,
+    mut commands: Commands,
+    world: &World,
+) {
+    for (entity, intention) in pick_up_q.iter() {
+        intention.resolve_intention(entity, &mut commands, world);
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (entity, intention) in drop_q.iter() {
+        intention.resolve_intention(entity, &mut commands, world);
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (entity, intention) in use_q.iter() {
+        intention.resolve_intention(entity, &mut commands, world);
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (entity, intention) in equip_q.iter() {
+        intention.resolve_intention(entity, &mut commands, world);
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (entity, intention) in unequip_q.iter() {
+        intention.resolve_intention(entity, &mut commands, world);
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,10 +1,7 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
 
-use crate::{
-    effects::prelude::PlayAudioEffect, IntentionKind, IntentionSourceId, MoveAction, MyAssets,
-    WalkingAudioEffect,
-};
+use crate::{IntentionKind, IntentionSourceId, MoveAction, MyAssets};
 
 use super::{IntentionResolver, IntentionSourceRef};
 
@@ -42,9 +39,6 @@ impl IntentionResolver for MoveIntention {
                 target_position: self.target_position,
                 entity: self.source.0,
             });
-            commands.add(PlayAudioEffect {
-                effect: WalkingAudioEffect::default(),
-            });
 
             commands.entity(e).despawn_recursive();
         } else {
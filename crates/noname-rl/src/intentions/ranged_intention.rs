@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{
+    Equipment, GameMap, Health, ItemTable, LogCategory, LogMessage, ProjectileAction, ProjectileHit,
+};
+
+use super::{IntentionBundle, IntentionResolver, IntentionSourceRef};
+
+/// Line of fire from `source` to `target`: the crossed tiles and the actor
+/// hit at the end of it, if any. Walls and actors stop the projectile.
+pub fn line_of_fire(
+    world: &World,
+    source: Entity,
+    target: TilePos,
+) -> Option<(Vec<TilePos>, Option<Entity>)> {
+    let source_pos = *world.get::<TilePos>(source)?;
+    // walls are checked by `projectile_path`, only the actors are needed
+    let actors = world
+        .iter_entities()
+        .filter(|entity| entity.id() != source && entity.contains::<Health>())
+        .filter_map(|entity| entity.get::<TilePos>().map(|pos| (entity.id(), *pos)))
+        .collect::<Vec<_>>();
+
+    let path = world
+        .resource::<GameMap>()
+        .projectile_path(source_pos, target, |pos| {
+            actors.iter().any(|(_, actor_pos)| actor_pos == pos)
+        });
+    let hit = path.last().and_then(|end| {
+        actors
+            .iter()
+            .find(|(_, pos)| pos == end)
+            .map(|(entity, _)| *entity)
+    });
+    Some((path, hit))
+}
+
+/// RangedAttackIntention
+///
+/// An actor wants to shoot at `target` with its equipped ranged weapon.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct RangedAttackIntention {
+    pub target: TilePos,
+    pub source: IntentionSourceRef,
+}
+
+impl IntentionResolver for RangedAttackIntention {
+    fn resolve_intention(
+        &self,
+        _e: Entity,
+        commands: &mut Commands,
+        world: &World,
+    ) -> Option<IntentionBundle> {
+        info!("RangedAttackIntention: {:?}", self);
+        let source = self.source.0;
+        let table = world.resource::<ItemTable>();
+        let range = match world
+            .get::<Equipment>(source)
+            .and_then(|equipment| equipment.ranged_weapon_range(table))
+        {
+            Some(range) => range,
+            None => {
+                info!("{:?} has no ranged weapon", source);
+                return None;
+            }
+        };
+
+        let source_pos = world.get::<TilePos>(source)?;
+        let distance = source_pos
+            .x
+            .abs_diff(self.target.x)
+            .max(source_pos.y.abs_diff(self.target.y));
+        if distance > range || !world.resource::<GameMap>().is_visible(&self.target) {
+            info!("{:?} is out of range", self.target);
+            commands.add(LogMessage::new(LogCategory::Blocked, "out of range"));
+            return None;
+        }
+
+        let (path, target) = line_of_fire(world, source, self.target)?;
+        commands.add(ProjectileAction {
+            source,
+            path,
+            target,
+            hit: ProjectileHit::Attack,
+            status: None,
+        });
+        None
+    }
+}
@@ -0,0 +1,341 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::StatusInflict;
+
+/// slots of a freshly created inventory
+pub const DEFAULT_INVENTORY_CAPACITY: usize = 12;
+
+/// What happens when an item is used. Using an item consumes one of it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ItemEffect {
+    /// restores `Health` of the user
+    Heal(i32),
+    /// moves the user to a random floor tile
+    Teleport,
+    /// reveals the whole map layout
+    RevealMap,
+    /// thrown at a tile in `range`, damages everyone within `radius`
+    Explode {
+        damage: i32,
+        radius: u32,
+        range: u32,
+    },
+    /// a projectile flying at a tile in `range`, damages the first actor hit
+    /// and may inflict a status on it
+    Bolt {
+        damage: i32,
+        range: u32,
+        #[serde(default)]
+        status: Option<StatusInflict>,
+    },
+    /// inflicts a status on the user
+    ApplyStatus(StatusInflict),
+}
+
+impl ItemEffect {
+    /// max distance of the target tile, `None` if the effect has no target
+    pub fn target_range(&self) -> Option<u32> {
+        match self {
+            ItemEffect::Explode { range, .. } | ItemEffect::Bolt { range, .. } => Some(*range),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Weapon,
+    Armour,
+    Shield,
+    Ring,
+}
+
+impl EquipSlot {
+    /// how many items of this kind can be worn at the same time
+    pub fn capacity(&self) -> usize {
+        match self {
+            EquipSlot::Ring => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Stat bonuses granted while the item is equipped.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipSlot,
+    #[serde(default)]
+    pub attack: i32,
+    #[serde(default)]
+    pub defense: i32,
+    #[serde(default)]
+    pub max_health: i32,
+    /// ranged weapons can hit targets up to `range` tiles away
+    #[serde(default)]
+    pub range: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StatBonus {
+    pub attack: i32,
+    pub defense: i32,
+    pub max_health: i32,
+}
+
+/// ItemDefinition
+///
+/// Static description of an item kind, loaded from `assets/data/items.ron`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    pub sprite: usize,
+    pub weight: f32,
+    pub max_stack: u32,
+    #[serde(default)]
+    pub spawn_weight: u32,
+    #[serde(default)]
+    pub key: Option<u32>,
+    #[serde(default)]
+    pub use_effect: Option<ItemEffect>,
+    #[serde(default)]
+    pub equip: Option<Equippable>,
+}
+
+#[derive(Debug, Clone, Resource, Deserialize)]
+pub struct ItemTable {
+    items: Vec<ItemDefinition>,
+}
+
+impl Default for ItemTable {
+    fn default() -> Self {
+        ron::from_str(include_str!("../assets/data/items.ron")).expect("invalid items.ron")
+    }
+}
+
+impl ItemTable {
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.items.iter()
+    }
+
+    /// item carrying the given key id, if any
+    pub fn key_item(&self, key: u32) -> Option<&ItemDefinition> {
+        self.items.iter().find(|item| item.key == Some(key))
+    }
+}
+
+/// A stack of items of the same kind, either lying on the map (together
+/// with a `TilePos`) or stored in an `Inventory`.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: String,
+    pub count: u32,
+}
+
+impl Item {
+    pub fn new(id: &str, count: u32) -> Self {
+        Self {
+            id: id.to_string(),
+            count,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    pub capacity: usize,
+    pub stacks: Vec<Item>,
+    pub selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(DEFAULT_INVENTORY_CAPACITY)
+    }
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            stacks: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// stores as many items as possible, filling the existing stacks first.
+    /// Returns how many items didn't fit.
+    pub fn add(&mut self, definition: &ItemDefinition, mut count: u32) -> u32 {
+        for stack in self.stacks.iter_mut().filter(|s| s.id == definition.id) {
+            let moved = count.min(definition.max_stack.saturating_sub(stack.count));
+            stack.count += moved;
+            count -= moved;
+        }
+
+        while count > 0 && self.stacks.len() < self.capacity {
+            let moved = count.min(definition.max_stack.max(1));
+            self.stacks.push(Item::new(&definition.id, moved));
+            count -= moved;
+        }
+
+        count
+    }
+
+    /// removes up to `count` items from a slot
+    pub fn take(&mut self, slot: usize, count: u32) -> Option<Item> {
+        let stack = self.stacks.get_mut(slot)?;
+        let taken = Item::new(&stack.id, count.min(stack.count));
+        stack.count -= taken.count;
+        if stack.count == 0 {
+            self.stacks.remove(slot);
+            self.selected = self.selected.min(self.stacks.len().saturating_sub(1));
+        }
+        Some(taken)
+    }
+
+    /// whether one more item of the kind fits, in a stack or a free slot
+    pub fn has_room(&self, definition: &ItemDefinition) -> bool {
+        self.stacks.len() < self.capacity
+            || self
+                .stacks
+                .iter()
+                .any(|stack| stack.id == definition.id && stack.count < definition.max_stack)
+    }
+
+    pub fn selected_stack(&self) -> Option<&Item> {
+        self.stacks.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.stacks.is_empty() {
+            self.selected = (self.selected + 1) % self.stacks.len();
+        }
+    }
+
+    pub fn weight(&self, table: &ItemTable) -> f32 {
+        self.stacks
+            .iter()
+            .filter_map(|stack| table.get(&stack.id).map(|d| d.weight * stack.count as f32))
+            .sum()
+    }
+
+    pub fn has_key(&self, key: u32, table: &ItemTable) -> bool {
+        self.stacks
+            .iter()
+            .any(|stack| table.get(&stack.id).map_or(false, |d| d.key == Some(key)))
+    }
+}
+
+/// Items worn by an actor, outside of its `Inventory`.
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub items: Vec<(EquipSlot, Item)>,
+}
+
+impl Equipment {
+    /// wears `item`, returning the item it replaced when the slot was full
+    pub fn equip(&mut self, slot: EquipSlot, item: Item) -> Option<Item> {
+        let worn = self.items.iter().filter(|(s, _)| *s == slot).count();
+        let replaced = if worn >= slot.capacity() {
+            let index = self.items.iter().position(|(s, _)| *s == slot)?;
+            Some(self.items.remove(index).1)
+        } else {
+            None
+        };
+        self.items.push((slot, item));
+        replaced
+    }
+
+    pub fn unequip(&mut self, index: usize) -> Option<Item> {
+        (index < self.items.len()).then(|| self.items.remove(index).1)
+    }
+
+    /// reach of the worn ranged weapon, if any
+    pub fn ranged_weapon_range(&self, table: &ItemTable) -> Option<u32> {
+        self.items
+            .iter()
+            .filter_map(|(_, item)| table.get(&item.id).and_then(|d| d.equip))
+            .find_map(|equip| equip.range)
+    }
+
+    /// sum of the bonuses of the worn items
+    pub fn bonus(&self, table: &ItemTable) -> StatBonus {
+        let mut bonus = StatBonus::default();
+        for equip in self
+            .items
+            .iter()
+            .filter_map(|(_, item)| table.get(&item.id).and_then(|d| d.equip))
+        {
+            bonus.attack += equip.attack;
+            bonus.defense += equip.defense;
+            bonus.max_health += equip.max_health;
+        }
+        bonus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn potion() -> ItemDefinition {
+        ItemDefinition {
+            id: "potion".to_string(),
+            name: "potion".to_string(),
+            sprite: 0,
+            weight: 1.,
+            max_stack: 5,
+            spawn_weight: 0,
+            key: None,
+            use_effect: None,
+            equip: None,
+        }
+    }
+
+    #[test]
+    fn add_overflows_into_new_slots() {
+        let mut inventory = Inventory::new(2);
+        assert_eq!(inventory.add(&potion(), 3), 0);
+        assert_eq!(inventory.add(&potion(), 4), 0);
+        assert_eq!(
+            inventory.stacks,
+            vec![Item::new("potion", 5), Item::new("potion", 2)]
+        );
+
+        // what doesn't fit in the last stack is left out
+        assert_eq!(inventory.add(&potion(), 9), 6);
+        assert_eq!(
+            inventory.stacks,
+            vec![Item::new("potion", 5), Item::new("potion", 5)]
+        );
+    }
+
+    #[test]
+    fn has_room_checks_the_stack_sizes() {
+        let mut inventory = Inventory::new(1);
+        assert!(inventory.has_room(&potion()));
+        inventory.add(&potion(), 4);
+        assert!(inventory.has_room(&potion()));
+        inventory.add(&potion(), 1);
+        assert!(!inventory.has_room(&potion()));
+    }
+
+    #[test]
+    fn take_empties_the_slot() {
+        let mut inventory = Inventory::new(2);
+        inventory.add(&potion(), 7);
+        inventory.selected = 1;
+
+        assert_eq!(inventory.take(1, 1), Some(Item::new("potion", 1)));
+        assert_eq!(inventory.stacks.len(), 2);
+        assert_eq!(inventory.take(1, 10), Some(Item::new("potion", 1)));
+        assert_eq!(inventory.stacks, vec![Item::new("potion", 5)]);
+        assert_eq!(inventory.selected, 0);
+        assert_eq!(inventory.take(1, 1), None);
+    }
+}
@@ -1,9 +1,12 @@
 use std::os::windows::process;
+use std::time::Duration;
 
 use bevy::{
-    input::common_conditions::{input_pressed, input_toggle_active},
+    app::ScheduleRunnerPlugin,
+    input::{common_conditions::input_toggle_active, InputSystem},
     prelude::*,
-    window::WindowResolution,
+    window::{ExitCondition, WindowResolution},
+    winit::WinitPlugin,
 };
 use bevy_asset_loader::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
@@ -11,37 +14,69 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_prototype_debug_lines::*;
 use bevy_prototype_lyon::prelude::*;
 use bevy_tweening::{lens::*, *};
-use events::{IntentionEndEvent, TileInfoEvent, TurnEndEvent};
-use intentions::{process_attack_intention, process_move_intention};
+use effects::prelude::*;
+use events::{
+    DamageEvent, DeathEvent, IntentionEndEvent, LevelUpEvent, ScreenShakeEvent, SoundEvent,
+    StatusAppliedEvent, StatusExpiredEvent, TurnEndEvent,
+};
+use intentions::{
+    process_attack_intention, process_door_intention, process_item_intention,
+    process_move_intention,
+};
 use leafwing_input_manager::prelude::*;
 use noise::*;
 
 mod actions;
 mod algorithms;
+mod bestiary;
 mod components;
 mod effects;
 mod events;
+mod game_log;
 mod intentions;
+mod items;
+mod map;
 mod query;
+mod replay;
 mod resources;
 mod room;
+mod save;
+mod settings;
+mod status;
 mod systems;
+mod terrain;
+mod undo;
 
 pub use actions::*;
 pub use algorithms::prelude::*;
+pub use bestiary::*;
 pub use query::*;
 
 pub use components::*;
-use resources::{RLRandomGenerator, RLTimeSystem};
+pub use game_log::*;
+pub use items::*;
+pub use map::*;
+pub use replay::*;
+use resources::{Dungeon, GameRng, RLRandomGenerator, RLTimeSystem};
+pub use save::*;
+pub use settings::*;
+pub use status::*;
 pub use systems::prelude::*;
+pub use terrain::*;
+pub use undo::*;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum GameState {
     #[default]
     AssetLoading,
+    MainMenu,
+    /// a new game is being set up
     AssetsLoaded,
     PlayerTurn,
     EnemyTurn,
+    Paused,
+    GameOver,
+    Victory,
 }
 
 #[derive(AssetCollection, Resource)]
@@ -67,47 +102,98 @@ pub struct MyAssets {
 }
 
 fn main() {
-    App::new()
-        .add_state::<GameState>()
-        .add_plugins(
-            DefaultPlugins
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: String::from("Noname RL"),
-                        resolution: WindowResolution::new(1500., 1000.),
-                        resize_constraints: WindowResizeConstraints {
-                            min_width: 1500.,
-                            min_height: 1000.,
-                            ..Default::default()
-                        },
-                        resizable: false,
-                        ..Default::default()
-                    }),
+    let replay_options = ReplayOptions::from_args();
 
-                    ..default()
-                })
-                .set(ImagePlugin::default_nearest()),
-        )
+    let default_plugins = DefaultPlugins.set(ImagePlugin::default_nearest());
+    let default_plugins = if replay_options.headless {
+        // replays are still rendered, just not shown
+        default_plugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .disable::<WinitPlugin>()
+    } else {
+        default_plugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: String::from("Noname RL"),
+                resolution: WindowResolution::new(1500., 1000.),
+                resize_constraints: WindowResizeConstraints {
+                    min_width: 960.,
+                    min_height: 640.,
+                    ..Default::default()
+                },
+                resizable: true,
+                ..Default::default()
+            }),
+
+            ..default()
+        })
+    };
+
+    let mut app = App::new();
+    app.add_state::<GameState>()
+        .add_plugins(default_plugins)
         .add_plugins(TilemapPlugin)
         .insert_resource(Msaa::Sample4)
         .insert_resource(RLTimeSystem::new())
         .insert_resource(RLRandomGenerator::new(Fbm::<Perlin>::new(0)))
+        .init_resource::<MapChunks>()
+        .init_resource::<ItemTable>()
+        .init_resource::<MonsterTable>()
+        .init_resource::<Targeting>()
+        .init_resource::<GameLog>()
+        .init_resource::<HoveredTile>()
+        .init_resource::<Dungeon>()
+        .init_resource::<Travel>()
+        .init_resource::<CameraMode>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<MusicTable>()
+        .init_resource::<GameRng>()
+        .init_resource::<SeedInput>()
+        .init_resource::<DebugConsole>()
+        .init_resource::<DebugFlags>()
+        .init_resource::<DebugOverlays>()
+        .init_resource::<FovDebug>()
+        .init_resource::<DungeonLayout>()
+        .insert_resource(AudioSettings::load())
         // events:
         .add_event::<TurnEndEvent>()
         .add_event::<IntentionEndEvent>()
-        .add_event::<TileInfoEvent>()
+        .add_event::<DamageEvent>()
+        .add_event::<ScreenShakeEvent>()
+        .add_event::<SoundEvent>()
+        .add_event::<DeathEvent>()
+        .add_event::<StatusAppliedEvent>()
+        .add_event::<StatusExpiredEvent>()
+        .add_event::<LevelUpEvent>()
         .add_plugins(DebugLinesPlugin::default())
         .add_plugins(ShapePlugin)
         .add_loading_state(
-            LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::AssetsLoaded),
+            LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::MainMenu),
         )
         .add_collection_to_loading_state::<_, MyAssets>(GameState::AssetLoading)
+        .add_systems(
+            OnEnter(GameState::MainMenu),
+            (teardown_game, main_menu_setup).chain(),
+        )
+        .add_systems(OnExit(GameState::MainMenu), despawn_menu_screen)
+        .add_systems(OnEnter(GameState::Paused), pause_menu_setup)
+        .add_systems(OnExit(GameState::Paused), despawn_menu_screen)
+        .add_systems(OnEnter(GameState::GameOver), game_over_setup)
+        .add_systems(OnExit(GameState::GameOver), despawn_menu_screen)
+        .add_systems(OnEnter(GameState::Victory), victory_setup)
+        .add_systems(OnExit(GameState::Victory), despawn_menu_screen)
         .add_systems(
             OnEnter(GameState::AssetsLoaded),
             (
+                teardown_game,
                 // use_my_assets,
                 game_ui_setup,
-                audio_effects_setup,
+                level_up_ui_setup,
+                options_ui_setup,
+                sound_bank_setup,
                 (
                     // apply_deferred,
                     setup_camera,
@@ -118,11 +204,15 @@ fn main() {
                     apply_deferred,
                     map_noise,
                     map_room_generator,
+                    minimap_setup,
                 )
                     .chain(),
                 apply_deferred,
                 spawn_monster,
+                spawn_items,
                 setup_input_handler.after(setup_player),
+                apply_deferred,
+                apply_saved_game.run_if(resource_exists::<PendingLoad>()),
             )
                 .chain(),
         )
@@ -130,36 +220,199 @@ fn main() {
         .add_plugins(
             WorldInspectorPlugin::new().run_if(input_toggle_active(false, KeyCode::Escape)),
         )
+        .add_systems(
+            PreUpdate,
+            debug_console_toggle
+                .after(InputSystem)
+                .before(InputManagerSystem::Update),
+        )
+        .add_systems(Update, (debug_console_ui, run_console_commands).chain())
+        .add_systems(Update, debug_overlay_panel)
+        .add_systems(
+            Update,
+            (
+                draw_fov_overlay,
+                draw_path_overlay,
+                draw_heatmap_overlay,
+                draw_room_overlay,
+                draw_occupancy_overlay,
+                update_ai_state_labels,
+            )
+                .after(TurnSet::View)
+                .run_if(resource_exists::<GameMap>()),
+        )
         // .add_systems(Startup, (setup_camera, setup_input_handler.after(setup_player)))
         // .add_systems(PreUpdate,)
         .add_systems(
-            Update,
+            OnEnter(GameState::PlayerTurn),
             (
-                update_player.run_if(state_exists_and_equals(GameState::PlayerTurn)),
-                update_enemies.run_if(state_exists_and_equals(GameState::EnemyTurn)),
-                camera_follow,
-                apply_deferred,
-                update_visibile_tiles,
-                apply_deferred,
-                process_move_intention,
-                process_attack_intention,
-                my_cursor_system.run_if(input_pressed(MouseButton::Right)),
+                tick_status_effects,
                 apply_deferred,
+                take_undo_snapshot.run_if(resource_exists::<UndoHistory>()),
             )
                 .chain(),
         )
+        .add_systems(OnEnter(GameState::EnemyTurn), update_enemies)
+        .configure_sets(
+            Update,
+            (
+                TurnSet::Input,
+                TurnSet::View,
+                TurnSet::Intentions,
+                TurnSet::Resolve,
+            )
+                .chain()
+                .run_if(resource_exists::<GameMap>()),
+        )
+        .add_systems(
+            Update,
+            (
+                (update_player, update_travel, update_targeting)
+                    .chain()
+                    .run_if(state_exists_and_equals(GameState::PlayerTurn))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .in_set(TurnSet::Input),
+                (
+                    update_camera_viewport,
+                    toggle_map_view,
+                    map_view_controls,
+                    camera_follow,
+                    update_map_chunks,
+                    apply_deferred,
+                    update_visibile_tiles,
+                    sync_map_chunks,
+                    update_minimap.run_if(resource_exists::<Minimap>()),
+                    apply_deferred,
+                )
+                    .chain()
+                    .in_set(TurnSet::View),
+                (
+                    process_move_intention,
+                    process_attack_intention,
+                    process_door_intention,
+                    process_item_intention,
+                    my_cursor_system,
+                    apply_deferred,
+                )
+                    .chain()
+                    .in_set(TurnSet::Intentions),
+                (
+                    update_effective_stats,
+                    update_deaths,
+                    detect_game_end,
+                    grant_experience,
+                    log_gameplay_events,
+                    spawn_combat_feedback,
+                    spawn_death_fades,
+                    gameplay_sounds,
+                )
+                    .chain()
+                    .in_set(TurnSet::Resolve),
+            ),
+        )
         .add_systems(
             PostUpdate,
             (
                 update_end_turn,
                 game_ui_update,
-                game_ui_player_position_update,
                 game_ui_interaction,
                 move_action_tween_end,
-                ui_update_on_query_tile_event,
+                despawn_finished_effects,
+                game_ui_inventory_update,
+                game_ui_character_update,
+                hud_health_update,
+                hud_stats_update,
+                hud_turn_update,
+                level_up_ui_update,
+                level_up_choice,
+                game_ui_log_scroll,
+                game_ui_log_update,
+                toggle_minimap,
+                (
+                    play_sounds,
+                    crossfade_music,
+                    save_audio_settings,
+                    options_ui_toggle,
+                    options_ui_interaction,
+                    options_ui_update,
+                ),
+                (
+                    toggle_pause.after(update_end_turn),
+                    menu_button_interaction,
+                    seed_input.run_if(state_exists_and_equals(GameState::MainMenu)),
+                ),
             ),
         )
+        .add_systems(
+            PostUpdate,
+            (
+                projectile_hit,
+                ui_update_on_query_tile_event,
+                update_minimap_markers,
+                minimap_click,
+                update_music,
+            )
+                .run_if(resource_exists::<GameMap>()),
+        )
         .add_plugins(TweeningPlugin)
+        .add_systems(
+            Update,
+            component_animator_system::<TextureAtlasSprite>
+                .in_set(AnimationSystem::AnimationUpdate),
+        )
         // .add_system s(PostUpdate, draw_game_ui)
-        .run();
+        .add_systems(
+            OnEnter(GameState::AssetsLoaded),
+            start_recording
+                .before(teardown_game)
+                .run_if(resource_exists::<ReplayRecorder>()),
+        )
+        .add_systems(
+            OnEnter(GameState::MainMenu),
+            start_playback
+                .after(main_menu_setup)
+                .run_if(resource_exists::<ReplayPlayback>()),
+        )
+        .add_systems(
+            Update,
+            (
+                undo_input
+                    .run_if(resource_exists::<UndoHistory>())
+                    .run_if(state_exists_and_equals(GameState::PlayerTurn))
+                    .in_set(TurnSet::Input),
+                replay_player_steps
+                    .run_if(resource_exists::<ReplayPlayback>())
+                    .run_if(state_exists_and_equals(GameState::PlayerTurn))
+                    .in_set(TurnSet::Input),
+                record_player_intentions
+                    .run_if(resource_exists::<ReplayRecorder>())
+                    .in_set(TurnSet::Intentions)
+                    .before(process_move_intention),
+            ),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                record_end_turn.before(update_end_turn),
+                save_recording.after(level_up_choice),
+            )
+                .run_if(resource_exists::<ReplayRecorder>()),
+        );
+
+    if let Some(recorder) = replay_options.recorder() {
+        app.insert_resource(recorder);
+    }
+    if let Some(playback) = replay_options.playback() {
+        app.insert_resource(playback);
+    }
+    // turns can only be undone while debugging
+    if cfg!(debug_assertions) {
+        app.init_resource::<UndoHistory>();
+    }
+    if replay_options.headless {
+        app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1. / 60.,
+        )));
+    }
+    app.run();
 }
@@ -0,0 +1,514 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::{
+    algorithms::{a_star, bresenham_line, dijkstra_map, tile_pos_to_world_pos},
+    DoorState, GameEntity, MyAssets, MyGameCamera, TerrainInfo, TileKind, TileMapLayer0,
+    TileMapVisibilityLayer,
+};
+
+/// side (in tiles) of a render chunk
+pub const CHUNK_SIZE: u32 = 32;
+
+/// number of extra chunks kept alive around the camera view
+const CHUNK_MARGIN: i32 = 1;
+
+/// texture indices of the visibility layer (see `out.png`)
+const UNSEEN_TEXTURE: u32 = 0;
+const REMEMBERED_TEXTURE: u32 = 1;
+const VISIBLE_TEXTURE: u32 = 2;
+
+/// GameMap
+///
+/// Logical map grid. This is the source of truth for the level layout and
+/// for what the player has seen: the tilemap entities are only a view of it
+/// and are spawned chunk by chunk around the camera.
+#[derive(Resource, Clone)]
+pub struct GameMap {
+    pub size: TilemapSize,
+    pub tile_size: TilemapTileSize,
+    pub grid_size: TilemapGridSize,
+    pub map_type: TilemapType,
+    kinds: Vec<TileKind>,
+    textures: Vec<u32>,
+    visited: Vec<bool>,
+    visible: Vec<bool>,
+    dirty_chunks: HashSet<IVec2>,
+    /// chunks changed since the minimap last redrew them
+    minimap_chunks: HashSet<IVec2>,
+}
+
+impl GameMap {
+    pub fn new(size: TilemapSize, tile_size: TilemapTileSize) -> Self {
+        let count = (size.x * size.y) as usize;
+        Self {
+            size,
+            tile_size,
+            grid_size: tile_size.into(),
+            map_type: TilemapType::default(),
+            kinds: vec![TileKind::Floor; count],
+            textures: vec![0; count],
+            visited: vec![false; count],
+            visible: vec![false; count],
+            dirty_chunks: HashSet::default(),
+            minimap_chunks: HashSet::default(),
+        }
+    }
+
+    fn index(&self, pos: &TilePos) -> usize {
+        (pos.y * self.size.x + pos.x) as usize
+    }
+
+    pub fn in_bounds(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.size.x as i32 && pos.y < self.size.y as i32
+    }
+
+    pub fn checked_pos(&self, pos: IVec2) -> Option<TilePos> {
+        if self.in_bounds(pos) {
+            Some(TilePos::new(pos.x as u32, pos.y as u32))
+        } else {
+            None
+        }
+    }
+
+    pub fn kind(&self, pos: &TilePos) -> TileKind {
+        self.kinds[self.index(pos)]
+    }
+
+    pub fn texture(&self, pos: &TilePos) -> u32 {
+        self.textures[self.index(pos)]
+    }
+
+    pub fn set(&mut self, pos: &TilePos, kind: TileKind, texture: u32) {
+        let idx = self.index(pos);
+        self.kinds[idx] = kind;
+        self.textures[idx] = texture;
+        self.mark_dirty(pos);
+    }
+
+    /// sets a tile using the sprite from the terrain table
+    pub fn set_terrain(&mut self, pos: &TilePos, kind: TileKind) {
+        let texture = kind.terrain().sprite_at(pos);
+        self.set(pos, kind, texture);
+    }
+
+    pub fn terrain(&self, pos: &TilePos) -> TerrainInfo {
+        self.kind(pos).terrain()
+    }
+
+    pub fn set_door(&mut self, pos: &TilePos, state: DoorState) {
+        self.set_terrain(pos, TileKind::Door(state));
+    }
+
+    pub fn door(&self, pos: &TilePos) -> Option<DoorState> {
+        match self.kind(pos) {
+            TileKind::Door(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// returns true if actors can't walk on the tile (closed doors must be
+    /// opened first)
+    pub fn is_blocked(&self, pos: &TilePos) -> bool {
+        !self.terrain(pos).walkable
+    }
+
+    /// returns true if the tile blocks the field of view
+    pub fn is_opaque(&self, pos: &TilePos) -> bool {
+        !self.terrain(pos).transparent
+    }
+
+    /// cost of walking on the tile for pathfinding, `None` if it can't be
+    /// entered. Closed (but not locked) doors can be opened on the way.
+    pub fn path_cost(&self, pos: &TilePos) -> Option<u32> {
+        match self.kind(pos) {
+            TileKind::Door(DoorState::Closed) => Some(2),
+            kind => {
+                let terrain = kind.terrain();
+                terrain.walkable.then_some(terrain.movement_cost)
+            }
+        }
+    }
+
+    /// cheapest path from `start` to `goal` (both included)
+    pub fn find_path(&self, start: TilePos, goal: TilePos) -> Option<Vec<TilePos>> {
+        a_star(start, goal, &self.size, |pos| self.path_cost(pos))
+    }
+
+    /// walking cost from `start` to every tile reachable for at most
+    /// `max_cost`
+    pub fn distance_map(
+        &self,
+        start: TilePos,
+        max_cost: u32,
+    ) -> std::collections::HashMap<TilePos, u32> {
+        dijkstra_map(start, &self.size, max_cost, |pos| self.path_cost(pos))
+    }
+
+    /// like `find_path`, but only through the tiles the player has seen
+    pub fn find_known_path(&self, start: TilePos, goal: TilePos) -> Option<Vec<TilePos>> {
+        a_star(start, goal, &self.size, |pos| {
+            self.is_visited(pos).then(|| self.path_cost(pos)).flatten()
+        })
+    }
+
+    /// tiles crossed by a projectile flying from `from` to `to` (`from`
+    /// excluded). The flight ends early on the first opaque tile or on the
+    /// first tile for which `blocked` returns true.
+    pub fn projectile_path(
+        &self,
+        from: TilePos,
+        to: TilePos,
+        blocked: impl Fn(&TilePos) -> bool,
+    ) -> Vec<TilePos> {
+        let start = IVec2::new(from.x as i32, from.y as i32);
+        let end = IVec2::new(to.x as i32, to.y as i32);
+
+        let mut path = Vec::new();
+        for pos in bresenham_line(start, end, &self.size).into_iter().skip(1) {
+            path.push(pos);
+            if self.is_opaque(&pos) || blocked(&pos) {
+                break;
+            }
+        }
+        path
+    }
+
+    pub fn is_visited(&self, pos: &TilePos) -> bool {
+        self.visited[self.index(pos)]
+    }
+
+    pub fn is_visible(&self, pos: &TilePos) -> bool {
+        self.visible[self.index(pos)]
+    }
+
+    /// marks a tile as visible (and therefore visited)
+    pub fn set_visible(&mut self, pos: &TilePos, visible: bool) {
+        let idx = self.index(pos);
+        if self.visible[idx] == visible && (!visible || self.visited[idx]) {
+            return;
+        }
+        self.visible[idx] = visible;
+        self.visited[idx] |= visible;
+        self.mark_dirty(pos);
+    }
+
+    /// marks a tile as visited without seeing it, when restoring a saved game
+    pub fn mark_visited(&mut self, pos: &TilePos) {
+        let idx = self.index(pos);
+        if !self.visited[idx] {
+            self.visited[idx] = true;
+            self.mark_dirty(pos);
+        }
+    }
+
+    /// marks every tile as visited, as if the player had seen the whole map
+    pub fn reveal_all(&mut self) {
+        self.visited.iter_mut().for_each(|visited| *visited = true);
+        self.mark_all_dirty();
+    }
+
+    /// shows or hides every tile at once, when the field of view is turned
+    /// off from the debug console
+    pub fn set_all_visible(&mut self, visible: bool) {
+        self.visible.iter_mut().for_each(|tile| *tile = visible);
+        if visible {
+            self.visited.iter_mut().for_each(|visited| *visited = true);
+        }
+        self.mark_all_dirty();
+    }
+
+    /// redraws the whole map, after it was replaced
+    pub fn mark_all_dirty(&mut self) {
+        for x in 0..self.chunk_count().x {
+            for y in 0..self.chunk_count().y {
+                self.dirty_chunks.insert(IVec2::new(x, y));
+                self.minimap_chunks.insert(IVec2::new(x, y));
+            }
+        }
+    }
+
+    pub fn visibility_texture(&self, pos: &TilePos) -> u32 {
+        let idx = self.index(pos);
+        if self.visible[idx] {
+            VISIBLE_TEXTURE
+        } else if self.visited[idx] {
+            REMEMBERED_TEXTURE
+        } else {
+            UNSEEN_TEXTURE
+        }
+    }
+
+    pub fn tiles_of_kind(&self, kind: TileKind) -> Vec<TilePos> {
+        let mut tiles = Vec::new();
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let pos = TilePos::new(x, y);
+                if self.kind(&pos) == kind {
+                    tiles.push(pos);
+                }
+            }
+        }
+        tiles
+    }
+
+    pub fn tile_to_world(&self, pos: &TilePos) -> Vec2 {
+        let pos = tile_pos_to_world_pos(pos, &self.size, &self.grid_size, &self.map_type)
+            .expect("unsupported map type");
+        Vec2::new(pos.x as f32, pos.y as f32)
+    }
+
+    /// world area covered by the map, tile borders included
+    pub fn world_bounds(&self) -> Rect {
+        let half_tile = Vec2::new(self.grid_size.x, self.grid_size.y) / 2.;
+        let last = TilePos::new(self.size.x - 1, self.size.y - 1);
+        Rect::from_corners(
+            self.tile_to_world(&TilePos::new(0, 0)) - half_tile,
+            self.tile_to_world(&last) + half_tile,
+        )
+    }
+
+    pub fn world_to_tile(&self, pos: &Vec2) -> Option<TilePos> {
+        TilePos::from_world_pos(pos, &self.size, &self.grid_size, &self.map_type)
+    }
+
+    pub fn chunk_of(pos: &TilePos) -> IVec2 {
+        IVec2::new((pos.x / CHUNK_SIZE) as i32, (pos.y / CHUNK_SIZE) as i32)
+    }
+
+    pub fn chunk_count(&self) -> IVec2 {
+        IVec2::new(
+            ((self.size.x + CHUNK_SIZE - 1) / CHUNK_SIZE) as i32,
+            ((self.size.y + CHUNK_SIZE - 1) / CHUNK_SIZE) as i32,
+        )
+    }
+
+    /// first tile and size of a chunk (chunks on the map border can be smaller)
+    pub fn chunk_extent(&self, chunk: IVec2) -> (TilePos, TilemapSize) {
+        let origin = TilePos::new(chunk.x as u32 * CHUNK_SIZE, chunk.y as u32 * CHUNK_SIZE);
+        let size = TilemapSize {
+            x: CHUNK_SIZE.min(self.size.x - origin.x),
+            y: CHUNK_SIZE.min(self.size.y - origin.y),
+        };
+        (origin, size)
+    }
+
+    fn mark_dirty(&mut self, pos: &TilePos) {
+        self.dirty_chunks.insert(Self::chunk_of(pos));
+        self.minimap_chunks.insert(Self::chunk_of(pos));
+    }
+
+    fn take_dirty_chunks(&mut self) -> HashSet<IVec2> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+
+    /// chunks to redraw on the minimap, tracked apart from the tilemap ones
+    pub fn take_minimap_chunks(&mut self) -> HashSet<IVec2> {
+        std::mem::take(&mut self.minimap_chunks)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MapChunk;
+
+#[derive(Debug, Clone, Copy)]
+struct ChunkLayers {
+    layer0: Entity,
+    visibility: Entity,
+}
+
+/// Render chunks currently spawned, keyed by chunk coordinates.
+#[derive(Resource, Default)]
+pub struct MapChunks {
+    spawned: HashMap<IVec2, ChunkLayers>,
+}
+
+impl MapChunks {
+    pub fn is_spawned(&self, chunk: IVec2) -> bool {
+        self.spawned.contains_key(&chunk)
+    }
+}
+
+fn spawn_chunk_layer(
+    commands: &mut Commands,
+    map: &GameMap,
+    chunk: IVec2,
+    texture: Handle<Image>,
+    z: f32,
+    texture_index: impl Fn(&TilePos) -> u32,
+) -> Entity {
+    let (origin, chunk_size) = map.chunk_extent(chunk);
+    let tilemap_entity = commands.spawn_empty().id();
+    let tilemap_id = TilemapId(tilemap_entity);
+    let mut tile_storage = TileStorage::empty(chunk_size);
+
+    commands.entity(tilemap_entity).with_children(|parent| {
+        for x in 0..chunk_size.x {
+            for y in 0..chunk_size.y {
+                let position = TilePos::new(x, y);
+                let map_pos = TilePos::new(origin.x + x, origin.y + y);
+                let tile_entity = parent
+                    .spawn(TileBundle {
+                        position,
+                        tilemap_id,
+                        texture_index: TileTextureIndex(texture_index(&map_pos)),
+                        ..Default::default()
+                    })
+                    .id();
+                tile_storage.set(&position, tile_entity);
+            }
+        }
+    });
+
+    commands.entity(tilemap_entity).insert((
+        TilemapBundle {
+            grid_size: map.grid_size,
+            map_type: map.map_type,
+            size: chunk_size,
+            storage: tile_storage,
+            texture: TilemapTexture::Single(texture),
+            tile_size: map.tile_size,
+            transform: Transform::from_xyz(
+                origin.x as f32 * map.grid_size.x,
+                origin.y as f32 * map.grid_size.y,
+                z,
+            ),
+            ..Default::default()
+        },
+        MapChunk,
+        GameEntity,
+    ));
+
+    tilemap_entity
+}
+
+/// Spawns the chunks overlapping the camera view and despawns the ones that
+/// went out of it.
+pub fn update_map_chunks(
+    map: Res<GameMap>,
+    assets: Res<MyAssets>,
+    mut chunks: ResMut<MapChunks>,
+    camera_q: Query<(&GlobalTransform, &OrthographicProjection), With<MyGameCamera>>,
+    mut commands: Commands,
+) {
+    let (camera_transform, projection) = match camera_q.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let center = camera_transform.translation().truncate();
+    let chunk_world_size = Vec2::new(
+        CHUNK_SIZE as f32 * map.grid_size.x,
+        CHUNK_SIZE as f32 * map.grid_size.y,
+    );
+    let min = ((center + projection.area.min) / chunk_world_size)
+        .floor()
+        .as_ivec2()
+        - IVec2::splat(CHUNK_MARGIN);
+    let max = ((center + projection.area.max) / chunk_world_size)
+        .floor()
+        .as_ivec2()
+        + IVec2::splat(CHUNK_MARGIN);
+    let min = min.max(IVec2::ZERO);
+    let max = max.min(map.chunk_count() - IVec2::ONE);
+
+    let in_view = |chunk: &IVec2| {
+        chunk.x >= min.x && chunk.y >= min.y && chunk.x <= max.x && chunk.y <= max.y
+    };
+
+    chunks.spawned.retain(|chunk, layers| {
+        if in_view(chunk) {
+            return true;
+        }
+        commands.entity(layers.layer0).despawn_recursive();
+        commands.entity(layers.visibility).despawn_recursive();
+        false
+    });
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            let chunk = IVec2::new(x, y);
+            if chunks.is_spawned(chunk) {
+                continue;
+            }
+
+            let layer0 = spawn_chunk_layer(
+                &mut commands,
+                &map,
+                chunk,
+                assets.player.clone(),
+                0.0,
+                |pos| map.texture(pos),
+            );
+            commands.entity(layer0).insert(TileMapLayer0);
+
+            let visibility = spawn_chunk_layer(
+                &mut commands,
+                &map,
+                chunk,
+                assets.visibility_image.clone(),
+                1.0,
+                |pos| map.visibility_texture(pos),
+            );
+            commands.entity(visibility).insert(TileMapVisibilityLayer);
+
+            chunks
+                .spawned
+                .insert(chunk, ChunkLayers { layer0, visibility });
+        }
+    }
+}
+
+/// Copies the textures of the changed map tiles into the spawned chunks.
+pub fn sync_map_chunks(
+    mut map: ResMut<GameMap>,
+    chunks: Res<MapChunks>,
+    storage_q: Query<&TileStorage, With<MapChunk>>,
+    mut tiles_q: Query<&mut TileTextureIndex>,
+) {
+    if !map.is_changed() {
+        return;
+    }
+
+    let dirty_chunks = map.bypass_change_detection().take_dirty_chunks();
+    for chunk in dirty_chunks {
+        let layers = match chunks.spawned.get(&chunk) {
+            Some(layers) => *layers,
+            None => continue,
+        };
+        let (origin, chunk_size) = map.chunk_extent(chunk);
+
+        // the storage is missing until the commands spawning the chunk are applied
+        let (layer0, visibility) = match (
+            storage_q.get(layers.layer0),
+            storage_q.get(layers.visibility),
+        ) {
+            (Ok(layer0), Ok(visibility)) => (layer0, visibility),
+            _ => continue,
+        };
+
+        for x in 0..chunk_size.x {
+            for y in 0..chunk_size.y {
+                let position = TilePos::new(x, y);
+                let map_pos = TilePos::new(origin.x + x, origin.y + y);
+
+                for (storage, texture) in [
+                    (layer0, map.texture(&map_pos)),
+                    (visibility, map.visibility_texture(&map_pos)),
+                ] {
+                    if let Some(mut index) = storage
+                        .get(&position)
+                        .and_then(|tile| tiles_q.get_mut(tile).ok())
+                    {
+                        if index.0 != texture {
+                            index.0 = texture;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::fs;
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_tweening::Animator;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::TurnEndEvent,
+    intentions::{
+        AttackIntention, DoorIntention, DropIntention, EquipIntention, IntentionSourceRef,
+        MoveIntention, PickUpIntention, RangedAttackIntention, UnequipIntention, UseItemIntention,
+    },
+    resources::{GameRng, RLTimeSystem},
+    Attack, Defense, Experience, GameMap, GameState, Health, Item, LevelUpChoice, LogCategory,
+    LogMessage, Monster, PendingLoad, Player, Projectile,
+};
+
+/// A decision of the player, with the entities it refers to replaced by
+/// tile positions and item ids.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayStep {
+    Move {
+        to: (u32, u32),
+    },
+    Attack {
+        at: (u32, u32),
+    },
+    Door {
+        at: (u32, u32),
+        open: bool,
+    },
+    PickUp {
+        item: String,
+    },
+    Drop {
+        slot: usize,
+    },
+    UseItem {
+        slot: usize,
+        target: Option<(u32, u32)>,
+    },
+    Equip {
+        slot: usize,
+    },
+    Unequip {
+        index: usize,
+    },
+    Fire {
+        at: (u32, u32),
+    },
+    LevelUp {
+        choice: LevelUpChoice,
+    },
+    EndTurn,
+}
+
+/// Replay
+///
+/// A recorded game: the seed of the dungeon and the steps of the player,
+/// with the turn they were taken on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub steps: Vec<(u32, ReplayStep)>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Option<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("can't read {}: {}", path, err);
+                return None;
+            }
+        };
+        match ron::from_str(&text) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                warn!("invalid {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("can't serialize the replay: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(path, text) {
+            warn!("can't write {}: {}", path, err);
+        }
+    }
+}
+
+/// Replay options of the command line:
+/// `--record <file>`, `--replay <file>`, `--until <turn>` and `--headless`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayOptions {
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    /// turn the playback stops at
+    pub until: Option<u32>,
+    /// plays the replay without a window, and exits once it is over
+    pub headless: bool,
+}
+
+impl ReplayOptions {
+    pub fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => options.record = args.next(),
+                "--replay" => options.replay = args.next(),
+                "--until" => options.until = args.next().and_then(|turn| turn.parse().ok()),
+                "--headless" => options.headless = true,
+                // the logger isn't set up yet
+                _ => eprintln!("unknown argument {}", arg),
+            }
+        }
+        options
+    }
+
+    pub fn recorder(&self) -> Option<ReplayRecorder> {
+        self.record.as_ref().map(|path| ReplayRecorder {
+            path: path.clone(),
+            replay: None,
+        })
+    }
+
+    pub fn playback(&self) -> Option<ReplayPlayback> {
+        let replay = Replay::load(self.replay.as_ref()?)?;
+        Some(ReplayPlayback {
+            replay,
+            next: 0,
+            until: self.until,
+            headless: self.headless,
+            started: false,
+        })
+    }
+}
+
+/// Records the steps of the player to `path`. Only new games are
+/// recorded: a continued one can't be generated again from its seed.
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayRecorder {
+    pub path: String,
+    pub replay: Option<Replay>,
+}
+
+impl ReplayRecorder {
+    pub fn record(&mut self, turn: u32, step: ReplayStep) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.steps.push((turn, step));
+        }
+    }
+}
+
+/// Plays a replay in place of the player's input.
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// index of the next step to play
+    pub next: usize,
+    pub until: Option<u32>,
+    pub headless: bool,
+    started: bool,
+}
+
+fn pos((x, y): (u32, u32)) -> TilePos {
+    TilePos { x, y }
+}
+
+/// Starts recording when a new game is set up.
+pub fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    game_rng: Res<GameRng>,
+    pending_load: Option<Res<PendingLoad>>,
+) {
+    if pending_load.is_some() {
+        warn!("a continued game can't be recorded");
+        recorder.replay = None;
+        return;
+    }
+    info!("recording to {} (seed {})", recorder.path, game_rng.seed);
+    recorder.replay = Some(Replay {
+        seed: game_rng.seed,
+        steps: Vec::new(),
+    });
+}
+
+type RecordedIntentions<'w, 's> = (
+    Query<'w, 's, &'static MoveIntention, Added<MoveIntention>>,
+    Query<'w, 's, &'static AttackIntention, Added<AttackIntention>>,
+    Query<'w, 's, &'static DoorIntention, Added<DoorIntention>>,
+    Query<'w, 's, &'static PickUpIntention, Added<PickUpIntention>>,
+    Query<'w, 's, &'static DropIntention, Added<DropIntention>>,
+    Query<'w, 's, &'static UseItemIntention, Added<UseItemIntention>>,
+    Query<'w, 's, &'static EquipIntention, Added<EquipIntention>>,
+    Query<'w, 's, &'static UnequipIntention, Added<UnequipIntention>>,
+    Query<'w, 's, &'static RangedAttackIntention, Added<RangedAttackIntention>>,
+);
+
+/// Records the intentions of the player before they are processed.
+pub fn record_player_intentions(
+    mut recorder: ResMut<ReplayRecorder>,
+    player_q: Query<Entity, With<Player>>,
+    items_q: Query<&Item>,
+    intentions: RecordedIntentions,
+    time_system: Res<RLTimeSystem>,
+) {
+    let player = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let (moves, attacks, doors, pick_ups, drops, uses, equips, unequips, shots) = intentions;
+    let turn = time_system.get_time();
+    let is_player = |source: &IntentionSourceRef| source.0 == player;
+
+    let mut steps = Vec::new();
+    for intention in moves.iter().filter(|i| is_player(&i.source)) {
+        let to = (intention.target.x, intention.target.y);
+        steps.push(ReplayStep::Move { to });
+    }
+    for intention in attacks.iter().filter(|i| is_player(&i.source)) {
+        let at = (intention.target_pos.x, intention.target_pos.y);
+        steps.push(ReplayStep::Attack { at });
+    }
+    for intention in doors.iter().filter(|i| is_player(&i.source)) {
+        steps.push(ReplayStep::Door {
+            at: (intention.target.x, intention.target.y),
+            open: intention.open,
+        });
+    }
+    for intention in pick_ups.iter().filter(|i| is_player(&i.source)) {
+        if let Ok(item) = items_q.get(intention.item) {
+            steps.push(ReplayStep::PickUp {
+                item: item.id.clone(),
+            });
+        }
+    }
+    for intention in drops.iter().filter(|i| is_player(&i.source)) {
+        steps.push(ReplayStep::Drop {
+            slot: intention.slot,
+        });
+    }
+    for intention in uses.iter().filter(|i| is_player(&i.source)) {
+        steps.push(ReplayStep::UseItem {
+            slot: intention.slot,
+            target: intention.target.map(|target| (target.x, target.y)),
+        });
+    }
+    for intention in equips.iter().filter(|i| is_player(&i.source)) {
+        steps.push(ReplayStep::Equip {
+            slot: intention.slot,
+        });
+    }
+    for intention in unequips.iter().filter(|i| is_player(&i.source)) {
+        steps.push(ReplayStep::Unequip {
+            index: intention.index,
+        });
+    }
+    for intention in shots.iter().filter(|i| is_player(&i.source)) {
+        let at = (intention.target.x, intention.target.y);
+        steps.push(ReplayStep::Fire { at });
+    }
+
+    for step in steps {
+        recorder.record(turn, step);
+    }
+}
+
+/// Records the end of the player's turns. Runs before `update_end_turn`
+/// moves the clock.
+pub fn record_end_turn(
+    mut recorder: ResMut<ReplayRecorder>,
+    mut end_turn_er: EventReader<TurnEndEvent>,
+    game_state: Res<State<GameState>>,
+    time_system: Res<RLTimeSystem>,
+) {
+    if end_turn_er.is_empty() {
+        return;
+    }
+    end_turn_er.clear();
+    if game_state.get() == &GameState::PlayerTurn {
+        recorder.record(time_system.get_time(), ReplayStep::EndTurn);
+    }
+}
+
+/// Writes the recording whenever a step was added.
+pub fn save_recording(recorder: Res<ReplayRecorder>) {
+    if !recorder.is_changed() {
+        return;
+    }
+    if let Some(replay) = recorder.replay.as_ref() {
+        replay.save(&recorder.path);
+    }
+}
+
+/// Skips the main menu and starts the game of the replay.
+pub fn start_playback(
+    mut playback: ResMut<ReplayPlayback>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    if playback.started {
+        return;
+    }
+    playback.started = true;
+    info!(
+        "replaying {} steps (seed {})",
+        playback.replay.steps.len(),
+        playback.replay.seed
+    );
+    commands.insert_resource(GameRng::new(playback.replay.seed));
+    next_state.set(GameState::AssetsLoaded);
+}
+
+type PendingIntentions = Or<(
+    With<MoveIntention>,
+    With<AttackIntention>,
+    With<DoorIntention>,
+    With<PickUpIntention>,
+    With<DropIntention>,
+    With<UseItemIntention>,
+    With<EquipIntention>,
+    With<UnequipIntention>,
+    With<RangedAttackIntention>,
+)>;
+
+/// Plays the next step of the replay once the previous one is over, in
+/// place of `update_player`. When the replay is over (or reaches the
+/// `until` turn) the player gets the control back, or the app exits when
+/// headless.
+pub fn replay_player_steps(
+    mut playback: ResMut<ReplayPlayback>,
+    player_q: Query<(Entity, &TilePos, &Transform, Option<&Animator<Transform>>), With<Player>>,
+    monsters_q: Query<(Entity, &TilePos), With<Monster>>,
+    items_q: Query<(Entity, &Item, &TilePos)>,
+    pending_q: Query<(), PendingIntentions>,
+    projectiles_q: Query<(), With<Projectile>>,
+    map: Res<GameMap>,
+    time_system: Res<RLTimeSystem>,
+    mut end_turn_ew: EventWriter<TurnEndEvent>,
+    mut exit: EventWriter<AppExit>,
+    mut commands: Commands,
+) {
+    let turn = time_system.get_time();
+    let reached_until = playback.until.map_or(false, |until| turn >= until);
+    if reached_until || playback.next >= playback.replay.steps.len() {
+        info!("replay over at turn {}", turn);
+        if playback.headless {
+            exit.send(AppExit);
+        } else {
+            commands.add(LogMessage::new(
+                LogCategory::Info,
+                "the replay is over, you have the control",
+            ));
+            commands.remove_resource::<ReplayPlayback>();
+        }
+        return;
+    }
+
+    let (player, player_pos, transform, animator) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    // waits for the previous step to be over, as the player would
+    let moving = animator.map_or(false, |animator| animator.tweenable().progress() < 1.);
+    if moving || !pending_q.is_empty() || !projectiles_q.is_empty() {
+        return;
+    }
+
+    let (step_turn, step) = playback.replay.steps[playback.next].clone();
+    playback.next += 1;
+    if step_turn != turn {
+        warn!(
+            "replay out of sync: step of turn {} played on turn {}",
+            step_turn, turn
+        );
+    }
+    info!("replay step {}: {:?}", playback.next, step);
+
+    let source = IntentionSourceRef(player);
+    match step {
+        ReplayStep::Move { to } => {
+            commands.spawn(MoveIntention {
+                target: pos(to),
+                source,
+                target_position: map.tile_to_world(&pos(to)).extend(transform.translation.z),
+            });
+        }
+        ReplayStep::Attack { at } => {
+            match monsters_q
+                .iter()
+                .find(|(_, monster_pos)| **monster_pos == pos(at))
+            {
+                Some((monster, _)) => {
+                    commands.spawn(AttackIntention {
+                        target: IntentionSourceRef(monster),
+                        source,
+                        target_pos: pos(at),
+                    });
+                }
+                None => warn!("replay: no monster to attack at {:?}", at),
+            }
+        }
+        ReplayStep::Door { at, open } => {
+            commands.spawn(DoorIntention {
+                target: pos(at),
+                source,
+                open,
+            });
+        }
+        ReplayStep::PickUp { item } => {
+            let found = items_q
+                .iter()
+                .find(|(_, found, item_pos)| found.id == item && *item_pos == player_pos);
+            match found {
+                Some((item, _, _)) => {
+                    commands.spawn(PickUpIntention { item, source });
+                }
+                None => warn!("replay: no {} to pick up at {:?}", item, player_pos),
+            }
+        }
+        ReplayStep::Drop { slot } => {
+            commands.spawn(DropIntention { slot, source });
+        }
+        ReplayStep::UseItem { slot, target } => {
+            commands.spawn(UseItemIntention {
+                slot,
+                source,
+                target: target.map(pos),
+            });
+        }
+        ReplayStep::Equip { slot } => {
+            commands.spawn(EquipIntention { slot, source });
+        }
+        ReplayStep::Unequip { index } => {
+            commands.spawn(UnequipIntention { index, source });
+        }
+        ReplayStep::Fire { at } => {
+            commands.spawn(RangedAttackIntention {
+                target: pos(at),
+                source,
+            });
+        }
+        ReplayStep::LevelUp { choice } => commands.add(move |world: &mut World| {
+            let mut player_q =
+                world.query::<(&mut Experience, &mut Health, &mut Attack, &mut Defense)>();
+            if let Ok((mut experience, mut health, mut attack, mut defense)) =
+                player_q.get_mut(world, player)
+            {
+                if experience.pending_level_ups > 0 {
+                    choice.apply(&mut health, &mut attack, &mut defense);
+                    experience.pending_level_ups -= 1;
+                }
+            }
+        }),
+        ReplayStep::EndTurn => end_turn_ew.send(TurnEndEvent),
+    }
+}
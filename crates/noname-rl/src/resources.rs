@@ -3,11 +3,55 @@ use std::fmt::Display;
 
 use bevy::{prelude::*, utils::HashMap};
 use noise::NoiseFn;
+use rand::{rngs::StdRng, SeedableRng};
 #[derive(Default, Clone, PartialEq, Resource)]
 pub struct GameContext {
     is_player_turn: bool,
 }
 
+/// Current dungeon level, 1 being the surface level.
+#[derive(Clone, Copy, PartialEq, Debug, Resource)]
+pub struct Dungeon {
+    pub depth: u32,
+}
+
+impl Default for Dungeon {
+    fn default() -> Self {
+        Self { depth: 1 }
+    }
+}
+
+/// GameRng
+///
+/// Random generator of the dungeon generation: the same seed builds the
+/// same dungeon.
+#[derive(Clone, Debug, Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// a generator for rolls made where the game rng can't be borrowed
+    /// mutably: the same seed and salt always roll the same
+    pub fn derive(&self, salt: u64) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ salt.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
 #[derive(Default, Clone, PartialEq, Resource)]
 pub struct RLRandomGenerator<T>
 where
@@ -22,17 +66,25 @@ impl<T: NoiseFn<f64, 2>> RLRandomGenerator<T> {
     }
 }
 
+/// game time steps in a turn, so hasted actors can act twice as often
+pub const TICKS_PER_TURN: u32 = 2;
+
+/// RLTimeSystem
+///
+/// Game clock counted in ticks, `TICKS_PER_TURN` of them making a turn.
 #[derive(Default, Clone, PartialEq, Resource)]
 pub struct RLTimeSystem {
     time: u32,
-    schedule: HashMap<u32, Vec<Entity>>,
+    /// tick at which the busy entities can act again
+    ready_at: HashMap<Entity, u32>,
 }
 
 impl Display for RLTimeSystem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hours = self.time / 3600;
-        let minutes = (self.time % 3600) / 60;
-        let seconds = self.time % 60;
+        let time = self.get_time();
+        let hours = time / 3600;
+        let minutes = (time % 3600) / 60;
+        let seconds = time % 60;
         write!(f, "{:02}:{:02}:{:02}", hours, minutes, seconds)
     }
 }
@@ -41,31 +93,36 @@ impl RLTimeSystem {
     pub fn new() -> Self {
         Self {
             time: 0,
-            schedule: HashMap::default(),
+            ready_at: HashMap::default(),
         }
     }
 
-    pub fn get_entities_at_time(&self, time: u32) -> Option<&Vec<Entity>> {
-        self.schedule.get(&time)
+    /// the entity can't act again before `dt` ticks
+    pub fn schedule_entity(&mut self, entity: Entity, dt: u32) {
+        self.ready_at.insert(entity, self.time + dt);
     }
 
-    pub fn get_entities_at_current_time(&self) -> Option<&Vec<Entity>> {
-        self.get_entities_at_time(self.time)
-    }
-    pub fn schedule_entity(&mut self, entity: Entity, dt: u32) {
-        let time = self.time + dt;
-        if let Some(entities) = self.schedule.get_mut(&time) {
-            entities.push(entity);
-        } else {
-            self.schedule.insert(time, vec![entity]);
-        }
+    /// whether the entity is done with its last action
+    pub fn is_ready(&self, entity: Entity) -> bool {
+        self.ready_at
+            .get(&entity)
+            .map_or(true, |ready_at| *ready_at <= self.time)
     }
 
     pub fn increment(&mut self) {
         self.time += 1;
+        let time = self.time;
+        self.ready_at.retain(|_, ready_at| *ready_at > time);
     }
 
+    /// current turn
     pub fn get_time(&self) -> u32 {
-        self.time
+        self.time / TICKS_PER_TURN
+    }
+
+    /// restores the clock of a saved game at the start of `time` turn
+    pub fn set_time(&mut self, time: u32) {
+        self.time = time * TICKS_PER_TURN;
+        self.ready_at.clear();
     }
 }
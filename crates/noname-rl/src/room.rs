@@ -50,6 +50,14 @@ impl Room {
             && self.pos.y + self.size.y as i32 >= other.pos.y
     }
 
+    pub fn pos(&self) -> IVec2 {
+        self.pos
+    }
+
+    pub fn size(&self) -> IVec2 {
+        self.size
+    }
+
     pub fn center(&self) -> IVec2 {
         IVec2::new(
             self.pos.x + self.size.x as i32 / 2,
@@ -57,9 +65,7 @@ impl Room {
         )
     }
 
-    pub fn create_random(width: i32, height: i32) -> Self {
-        let mut rng = rand::thread_rng();
-
+    pub fn create_random(rng: &mut impl Rng, width: i32, height: i32) -> Self {
         let x = rng.gen_range(0..width);
         let y = rng.gen_range(0..height);
 
@@ -70,12 +76,11 @@ impl Room {
     }
 
     pub fn create_random_in_rect(
+        rng: &mut impl Rng,
         top_left: IVec2,
         size: IVec2,
         room_size_range: (Range<u32>, Range<u32>),
     ) -> Self {
-        let mut rng = rand::thread_rng();
-
         let x = rng.gen_range(top_left.x..top_left.x + size.x);
         let y = rng.gen_range(top_left.y..top_left.y + size.y);
 
@@ -0,0 +1,352 @@
+use std::fs;
+
+use bevy::{
+    ecs::system::{Command, CommandQueue},
+    prelude::*,
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    resources::{Dungeon, GameRng, RLTimeSystem},
+    spawn_item, spawn_monster_at, Attack, Defense, DoorState, Equipment, Experience, GameMap,
+    Health, Inventory, Item, Monster, MonsterKind, MonsterTable, MyAssets, NeedsFovUpdate, Player,
+    StatusEffects,
+};
+
+/// file the running game is saved to, removed once the game is over
+pub const SAVE_PATH: &str = "savegame.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub pos: (u32, u32),
+    pub health: Health,
+    pub attack: Attack,
+    pub defense: Defense,
+    pub inventory: Inventory,
+    pub equipment: Equipment,
+    pub experience: Experience,
+    pub status_effects: StatusEffects,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMonster {
+    pub kind: String,
+    pub pos: (u32, u32),
+    pub health: Health,
+    pub status_effects: StatusEffects,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub item: Item,
+    pub pos: (u32, u32),
+}
+
+/// SaveGame
+///
+/// The dungeon itself is not saved: it is generated again from `seed`, then
+/// what changed since (doors, explored tiles, actors, items) is restored on
+/// top of it by `apply_saved_game`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub seed: u64,
+    pub depth: u32,
+    pub time: u32,
+    /// visited tiles in map order, as (count, visited) runs
+    pub visited: Vec<(u32, bool)>,
+    pub doors: Vec<((u32, u32), DoorState)>,
+    pub player: SavedPlayer,
+    pub monsters: Vec<SavedMonster>,
+    pub items: Vec<SavedItem>,
+}
+
+/// A save to restore once the new game has been generated.
+#[derive(Resource, Debug, Clone)]
+pub struct PendingLoad(pub SaveGame);
+
+impl SaveGame {
+    pub fn exists() -> bool {
+        fs::metadata(SAVE_PATH).is_ok()
+    }
+
+    pub fn load() -> Option<Self> {
+        let text = fs::read_to_string(SAVE_PATH).ok()?;
+        match ron::from_str(&text) {
+            Ok(save) => Some(save),
+            Err(err) => {
+                warn!("invalid {}: {}", SAVE_PATH, err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("can't serialize the game: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(SAVE_PATH, text) {
+            warn!("can't write {}: {}", SAVE_PATH, err);
+        }
+    }
+
+    pub fn delete() {
+        if SaveGame::exists() {
+            if let Err(err) = fs::remove_file(SAVE_PATH) {
+                warn!("can't remove {}: {}", SAVE_PATH, err);
+            }
+        }
+    }
+
+    /// the running game, if there is one
+    pub fn capture(world: &mut World) -> Option<Self> {
+        let player = world
+            .query_filtered::<(
+                &TilePos,
+                &Health,
+                &Attack,
+                &Defense,
+                &Inventory,
+                &Equipment,
+                &Experience,
+                &StatusEffects,
+            ), With<Player>>()
+            .get_single(world)
+            .ok()
+            .map(
+                |(pos, health, attack, defense, inventory, equipment, experience, effects)| {
+                    SavedPlayer {
+                        pos: (pos.x, pos.y),
+                        health: *health,
+                        attack: *attack,
+                        defense: *defense,
+                        inventory: inventory.clone(),
+                        equipment: equipment.clone(),
+                        experience: experience.clone(),
+                        status_effects: effects.clone(),
+                    }
+                },
+            )?;
+        let monsters = world
+            .query_filtered::<(&MonsterKind, &TilePos, &Health, &StatusEffects), With<Monster>>()
+            .iter(world)
+            .filter(|(_, _, health, _)| health.current > 0)
+            .map(|(kind, pos, health, effects)| SavedMonster {
+                kind: kind.id.clone(),
+                pos: (pos.x, pos.y),
+                health: *health,
+                status_effects: effects.clone(),
+            })
+            .collect();
+        let items = world
+            .query::<(&Item, &TilePos)>()
+            .iter(world)
+            .map(|(item, pos)| SavedItem {
+                item: item.clone(),
+                pos: (pos.x, pos.y),
+            })
+            .collect();
+
+        let map = world.get_resource::<GameMap>()?;
+        let mut doors = Vec::new();
+        for y in 0..map.size.y {
+            for x in 0..map.size.x {
+                if let Some(state) = map.door(&TilePos { x, y }) {
+                    doors.push(((x, y), state));
+                }
+            }
+        }
+
+        Some(Self {
+            seed: world.resource::<GameRng>().seed,
+            depth: world.resource::<Dungeon>().depth,
+            time: world.resource::<RLTimeSystem>().get_time(),
+            visited: encode_visited(map),
+            doors,
+            player,
+            monsters,
+            items,
+        })
+    }
+
+    /// Puts the saved player, monsters and items in place of the current
+    /// ones.
+    pub fn restore_actors(&self, world: &mut World) {
+        // the current monsters and items are replaced by the saved ones
+        let current = world
+            .query_filtered::<Entity, Or<(With<Monster>, With<Item>)>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for entity in current {
+            world.entity_mut(entity).despawn_recursive();
+        }
+
+        let player = self.player.clone();
+        let player_tile = TilePos {
+            x: player.pos.0,
+            y: player.pos.1,
+        };
+        let player_pos = world.resource::<GameMap>().tile_to_world(&player_tile);
+        let player_entity = world
+            .query_filtered::<Entity, With<Player>>()
+            .get_single(world)
+            .ok();
+        if let Some(entity) = player_entity {
+            let mut entity = world.entity_mut(entity);
+            entity.insert((
+                player_tile,
+                player.health,
+                player.attack,
+                player.defense,
+                player.inventory,
+                player.equipment,
+                player.experience,
+                player.status_effects,
+                NeedsFovUpdate,
+            ));
+            if let Some(mut transform) = entity.get_mut::<Transform>() {
+                transform.translation.x = player_pos.x;
+                transform.translation.y = player_pos.y;
+            }
+        }
+
+        let mut queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut queue, world);
+            let assets = world.resource::<MyAssets>();
+            let map = world.resource::<GameMap>();
+            let table = world.resource::<MonsterTable>();
+            for monster in self.monsters.iter() {
+                let definition = match table.get(&monster.kind) {
+                    Some(definition) => definition,
+                    None => {
+                        warn!("unknown saved monster {}", monster.kind);
+                        continue;
+                    }
+                };
+                let tile_pos = TilePos {
+                    x: monster.pos.0,
+                    y: monster.pos.1,
+                };
+                let entity = spawn_monster_at(&mut commands, assets, map, definition, tile_pos);
+                commands
+                    .entity(entity)
+                    .insert((monster.health, monster.status_effects.clone()));
+            }
+        }
+        queue.apply(world);
+
+        for saved in self.items.iter() {
+            let tile_pos = TilePos {
+                x: saved.pos.0,
+                y: saved.pos.1,
+            };
+            spawn_item(world, saved.item.clone(), tile_pos);
+        }
+    }
+}
+
+/// visited tiles in map order, as (count, visited) runs
+fn encode_visited(map: &GameMap) -> Vec<(u32, bool)> {
+    let mut runs: Vec<(u32, bool)> = Vec::new();
+    for y in 0..map.size.y {
+        for x in 0..map.size.x {
+            let is_visited = map.is_visited(&TilePos { x, y });
+            match runs.last_mut() {
+                Some((count, run)) if *run == is_visited => *count += 1,
+                _ => runs.push((1, is_visited)),
+            }
+        }
+    }
+    runs
+}
+
+/// marks the tiles of the visited runs of `encode_visited`
+fn decode_visited(map: &mut GameMap, runs: &[(u32, bool)]) {
+    let width = map.size.x;
+    let mut index = 0;
+    for (count, visited) in runs.iter() {
+        if *visited {
+            for i in index..index + count {
+                map.mark_visited(&TilePos {
+                    x: i % width,
+                    y: i / width,
+                });
+            }
+        }
+        index += count;
+    }
+}
+
+/// Saves the running game to `SAVE_PATH`.
+pub struct SaveGameCommand;
+
+impl Command for SaveGameCommand {
+    fn apply(self, world: &mut World) {
+        match SaveGame::capture(world) {
+            Some(save) => {
+                info!("saving the game to {}", SAVE_PATH);
+                save.save();
+            }
+            None => warn!("no game to save"),
+        }
+    }
+}
+
+/// Restores the `PendingLoad` save over the freshly generated dungeon.
+pub fn apply_saved_game(world: &mut World) {
+    let save = match world.remove_resource::<PendingLoad>() {
+        Some(PendingLoad(save)) => save,
+        None => return,
+    };
+
+    world.resource_mut::<Dungeon>().depth = save.depth;
+    world.resource_mut::<RLTimeSystem>().set_time(save.time);
+    {
+        let mut map = world.resource_mut::<GameMap>();
+        for ((x, y), state) in save.doors.iter() {
+            map.set_door(&TilePos { x: *x, y: *y }, *state);
+        }
+        decode_visited(&mut map, &save.visited);
+    }
+
+    save.restore_actors(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_tilemap::prelude::{TilemapSize, TilemapTileSize};
+
+    use super::*;
+
+    fn new_map() -> GameMap {
+        GameMap::new(
+            TilemapSize { x: 4, y: 3 },
+            TilemapTileSize { x: 16., y: 16. },
+        )
+    }
+
+    #[test]
+    fn visited_runs_round_trip() {
+        let mut map = new_map();
+        for (x, y) in [(1, 0), (2, 0), (3, 0), (0, 1), (3, 2)] {
+            map.mark_visited(&TilePos { x, y });
+        }
+
+        let runs = encode_visited(&map);
+        assert_eq!(runs, vec![(1, false), (4, true), (6, false), (1, true)]);
+
+        let mut restored = new_map();
+        decode_visited(&mut restored, &runs);
+        for y in 0..3 {
+            for x in 0..4 {
+                let pos = TilePos { x, y };
+                assert_eq!(restored.is_visited(&pos), map.is_visited(&pos));
+            }
+        }
+    }
+}
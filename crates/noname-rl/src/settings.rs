@@ -0,0 +1,113 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// file the settings are kept in, next to the executable's working directory
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+/// step of the volume buttons of the options menu
+pub const VOLUME_STEP: f32 = 0.1;
+
+/// Volume channels of the options menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChannel {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl VolumeChannel {
+    pub const ALL: [VolumeChannel; 3] = [
+        VolumeChannel::Master,
+        VolumeChannel::Music,
+        VolumeChannel::Sfx,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            VolumeChannel::Master => "master",
+            VolumeChannel::Music => "music",
+            VolumeChannel::Sfx => "sound effects",
+        }
+    }
+}
+
+/// AudioSettings
+///
+/// Volumes between 0 and 1, saved to `SETTINGS_PATH` whenever they change.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 0.8,
+            music: 0.6,
+            sfx: 0.8,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// the saved settings, or the default ones if there are none yet
+    pub fn load() -> Self {
+        match fs::read_to_string(SETTINGS_PATH) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("invalid {}: {}", SETTINGS_PATH, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("can't serialize the settings: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(SETTINGS_PATH, text) {
+            warn!("can't write {}: {}", SETTINGS_PATH, err);
+        }
+    }
+
+    pub fn get(&self, channel: VolumeChannel) -> f32 {
+        match channel {
+            VolumeChannel::Master => self.master,
+            VolumeChannel::Music => self.music,
+            VolumeChannel::Sfx => self.sfx,
+        }
+    }
+
+    /// changes a volume by `delta`, keeping it between 0 and 1
+    pub fn adjust(&mut self, channel: VolumeChannel, delta: f32) {
+        let volume = match channel {
+            VolumeChannel::Master => &mut self.master,
+            VolumeChannel::Music => &mut self.music,
+            VolumeChannel::Sfx => &mut self.sfx,
+        };
+        // rounded so repeated steps don't drift
+        *volume = ((*volume + delta).clamp(0., 1.) * 100.).round() / 100.;
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+pub fn save_audio_settings(settings: Res<AudioSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::TICKS_PER_TURN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// loses `magnitude` health every tick
+    Poisoned,
+    /// loses `magnitude` health every tick
+    Burning,
+    /// loses its turns
+    Stunned,
+    /// actions take twice as long
+    Slowed,
+    /// actions take half as long
+    Hasted,
+    /// gains `magnitude` health every tick
+    Regenerating,
+    /// moves in random directions
+    Confused,
+}
+
+/// How a status combines with an already active status of the same kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stacking {
+    /// magnitudes add up, the longest duration is kept
+    Intensity,
+    /// the strongest magnitude and the longest duration are kept
+    Refresh,
+    /// durations add up
+    Extend,
+}
+
+impl StatusKind {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Poisoned => Stacking::Intensity,
+            StatusKind::Burning | StatusKind::Regenerating => Stacking::Refresh,
+            StatusKind::Stunned
+            | StatusKind::Slowed
+            | StatusKind::Hasted
+            | StatusKind::Confused => Stacking::Extend,
+        }
+    }
+
+    /// game time between two ticks of a periodic status, `None` if the
+    /// status has no periodic effect
+    pub fn tick_interval(&self) -> Option<u32> {
+        match self {
+            StatusKind::Poisoned | StatusKind::Burning => Some(1),
+            StatusKind::Regenerating => Some(2),
+            _ => None,
+        }
+    }
+
+    /// status removed when this one is applied
+    pub fn cancels(&self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Slowed => Some(StatusKind::Hasted),
+            StatusKind::Hasted => Some(StatusKind::Slowed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: i32,
+    /// game time at which the status ends
+    pub expires_at: u32,
+    /// game time of the next periodic tick
+    pub next_tick: u32,
+    /// who inflicted the status, credited for the damage it deals (not
+    /// saved, entities don't outlive the game)
+    #[serde(skip)]
+    pub source: Option<Entity>,
+}
+
+/// Statuses currently affecting an actor. Ticked and expired by
+/// `tick_status_effects` as the `RLTimeSystem` advances.
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// adds a status lasting `duration` from `now`, following the stacking
+    /// rules of its kind
+    pub fn apply(
+        &mut self,
+        kind: StatusKind,
+        duration: u32,
+        magnitude: i32,
+        source: Option<Entity>,
+        now: u32,
+    ) {
+        if let Some(cancelled) = kind.cancels() {
+            self.effects.retain(|effect| effect.kind != cancelled);
+        }
+
+        let expires_at = now + duration;
+        match self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => {
+                match kind.stacking() {
+                    Stacking::Intensity => {
+                        effect.magnitude += magnitude;
+                        effect.expires_at = effect.expires_at.max(expires_at);
+                    }
+                    Stacking::Refresh => {
+                        effect.magnitude = effect.magnitude.max(magnitude);
+                        effect.expires_at = effect.expires_at.max(expires_at);
+                    }
+                    Stacking::Extend => effect.expires_at += duration,
+                }
+                effect.source = source.or(effect.source);
+            }
+            None => self.effects.push(StatusEffect {
+                kind,
+                magnitude,
+                expires_at,
+                next_tick: now + kind.tick_interval().unwrap_or(0),
+                source,
+            }),
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// ticks needed for an action taking `cost` turns
+    pub fn scale_time(&self, cost: u32) -> u32 {
+        let ticks = cost * TICKS_PER_TURN;
+        if self.has(StatusKind::Slowed) {
+            ticks * 2
+        } else if self.has(StatusKind::Hasted) {
+            (ticks / 2).max(1)
+        } else {
+            ticks
+        }
+    }
+
+    /// removes the statuses ended at `now`, returning their kinds
+    pub fn expire(&mut self, now: u32) -> Vec<StatusKind> {
+        let expired = self
+            .effects
+            .iter()
+            .filter(|effect| effect.expires_at <= now)
+            .map(|effect| effect.kind)
+            .collect();
+        self.effects.retain(|effect| effect.expires_at > now);
+        expired
+    }
+}
+
+/// A status inflicted by an item or an attack.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct StatusInflict {
+    pub kind: StatusKind,
+    pub duration: u32,
+    #[serde(default)]
+    pub magnitude: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(effects: &StatusEffects, kind: StatusKind) -> (i32, u32) {
+        let effect = effects.effects.iter().find(|e| e.kind == kind).unwrap();
+        (effect.magnitude, effect.expires_at)
+    }
+
+    #[test]
+    fn apply_stacks_by_kind() {
+        let mut effects = StatusEffects::default();
+
+        // intensity: magnitudes add up, the longest duration is kept
+        effects.apply(StatusKind::Poisoned, 5, 1, None, 0);
+        effects.apply(StatusKind::Poisoned, 3, 2, None, 1);
+        assert_eq!(effect(&effects, StatusKind::Poisoned), (3, 5));
+
+        // refresh: the strongest magnitude and the longest duration are kept
+        effects.apply(StatusKind::Burning, 2, 3, None, 0);
+        effects.apply(StatusKind::Burning, 4, 1, None, 1);
+        assert_eq!(effect(&effects, StatusKind::Burning), (3, 5));
+
+        // extend: durations add up
+        effects.apply(StatusKind::Stunned, 2, 0, None, 0);
+        effects.apply(StatusKind::Stunned, 3, 0, None, 1);
+        assert_eq!(effect(&effects, StatusKind::Stunned), (0, 5));
+
+        assert_eq!(effects.effects.len(), 3);
+    }
+
+    #[test]
+    fn apply_cancels_the_opposite_status() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusKind::Slowed, 5, 0, None, 0);
+        effects.apply(StatusKind::Hasted, 5, 0, None, 0);
+        assert!(!effects.has(StatusKind::Slowed));
+        assert!(effects.has(StatusKind::Hasted));
+    }
+}
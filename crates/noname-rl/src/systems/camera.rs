@@ -0,0 +1,341 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+    render::camera::Viewport,
+    window::{PrimaryWindow, WindowResized, WindowScaleFactorChanged},
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use leafwing_input_manager::prelude::*;
+
+use rand::Rng;
+
+use crate::{events::ScreenShakeEvent, GameMap, MyGameCamera, Player, RLAction};
+
+/// share of the window width taken by the game view, the side panel gets
+/// the rest
+pub const GAME_VIEW_PERCENT: f32 = 70.;
+
+/// zoom of the camera following the player
+pub const FOLLOW_SCALE: f32 = 0.5;
+/// closest zoom of the map view
+const MIN_SCALE: f32 = 0.25;
+/// zoom factor of a mouse wheel step
+const ZOOM_STEP: f32 = 1.15;
+/// map view panning with the keys, in screen pixels per second
+const PAN_SPEED: f32 = 600.;
+
+/// CameraSettings
+///
+/// Tuning of the camera following the player.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+    /// area around the camera center, in world units, in which the player
+    /// moves without the camera following
+    pub dead_zone: Vec2,
+    /// how fast the camera catches up, higher is snappier
+    pub smoothing: f32,
+    pub shake_enabled: bool,
+    /// camera offset at full trauma, in world units
+    pub max_shake: f32,
+    /// trauma lost per second
+    pub shake_decay: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: Vec2::new(64., 48.),
+            smoothing: 8.,
+            shake_enabled: true,
+            max_shake: 8.,
+            shake_decay: 1.5,
+        }
+    }
+}
+
+/// CameraController
+///
+/// State of the game camera: the point it looks at, before the shake is
+/// added, and the current shake trauma.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CameraController {
+    pub focus: Vec2,
+    pub trauma: f32,
+}
+
+/// CameraMode
+///
+/// Either the camera follows the player or the player looks around the map
+/// (the player can't act meanwhile).
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    Follow,
+    MapView,
+}
+
+/// Camera position keeping the view inside `bounds`: on an axis where the
+/// view is larger than the bounds, the camera is centered on them.
+pub fn clamp_camera(position: Vec2, view_size: Vec2, bounds: Rect) -> Vec2 {
+    let min = bounds.min + view_size / 2.;
+    let max = bounds.max - view_size / 2.;
+    let center = bounds.center();
+    Vec2::new(
+        if min.x > max.x {
+            center.x
+        } else {
+            position.x.clamp(min.x, max.x)
+        },
+        if min.y > max.y {
+            center.y
+        } else {
+            position.y.clamp(min.y, max.y)
+        },
+    )
+}
+
+/// Game view in the top left corner of the window, over its whole height.
+/// Viewports are in physical pixels, so the window scale factor is taken
+/// into account.
+pub fn game_viewport(window: &Window) -> Viewport {
+    let width = (window.physical_width() as f32 * GAME_VIEW_PERCENT / 100.) as u32;
+    Viewport {
+        physical_position: UVec2::ZERO,
+        physical_size: UVec2::new(width.max(1), window.physical_height().max(1)),
+        ..Default::default()
+    }
+}
+
+/// Recomputes the game viewport when the window is resized or moved to a
+/// screen with another scale factor.
+pub fn update_camera_viewport(
+    mut resized_events: EventReader<WindowResized>,
+    mut scale_events: EventReader<WindowScaleFactorChanged>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<&mut Camera, With<MyGameCamera>>,
+) {
+    let changed = resized_events.iter().count() + scale_events.iter().count() > 0;
+    if !changed {
+        return;
+    }
+    let (window, mut camera) = match (q_windows.get_single(), camera_q.get_single_mut()) {
+        (Ok(window), Ok(camera)) => (window, camera),
+        _ => return,
+    };
+    let viewport = game_viewport(window);
+    info!("game viewport: {:?}", viewport.physical_size);
+    camera.viewport = Some(viewport);
+}
+
+/// world area covered by the tiles the player has seen
+fn explored_bounds(map: &GameMap) -> Option<Rect> {
+    let mut bounds: Option<Rect> = None;
+    for y in 0..map.size.y {
+        for x in 0..map.size.x {
+            let pos = TilePos::new(x, y);
+            if !map.is_visited(&pos) {
+                continue;
+            }
+            let point = map.tile_to_world(&pos);
+            bounds = Some(match bounds {
+                Some(bounds) => bounds.union_point(point),
+                None => Rect::from_center_size(point, Vec2::ZERO),
+            });
+        }
+    }
+    bounds.map(|bounds| bounds.inset(map.grid_size.x))
+}
+
+/// zoom at which the whole map fits in the viewport
+fn max_scale(map: &GameMap, viewport: Vec2) -> f32 {
+    let map_size = map.world_bounds().size();
+    (map_size.x / viewport.x).max(map_size.y / viewport.y)
+}
+
+/// Switches between following the player and the map view, which starts
+/// zoomed out on the explored part of the level.
+pub fn toggle_map_view(
+    action_q: Query<&ActionState<RLAction>, With<Player>>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_q: Query<(&Camera, &mut Transform, &mut OrthographicProjection), With<MyGameCamera>>,
+    map: Res<GameMap>,
+) {
+    let action = match action_q.get_single() {
+        Ok(action) => action,
+        Err(_) => return,
+    };
+    let leave = *camera_mode == CameraMode::MapView && action.just_pressed(RLAction::Cancel);
+    if !action.just_pressed(RLAction::MapView) && !leave {
+        return;
+    }
+    let (camera, mut transform, mut projection) = match camera_q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    match *camera_mode {
+        CameraMode::Follow => {
+            let viewport = match camera.logical_viewport_size() {
+                Some(viewport) => viewport,
+                None => return,
+            };
+            let explored = explored_bounds(&map).unwrap_or(map.world_bounds());
+            let fit = (explored.width() / viewport.x).max(explored.height() / viewport.y);
+            projection.scale = fit.clamp(FOLLOW_SCALE, max_scale(&map, viewport));
+            let position = clamp_camera(
+                explored.center(),
+                viewport * projection.scale,
+                map.world_bounds(),
+            );
+            transform.translation = position.extend(transform.translation.z);
+            *camera_mode = CameraMode::MapView;
+        }
+        CameraMode::MapView => {
+            // `camera_follow` puts the camera back on the player
+            projection.scale = FOLLOW_SCALE;
+            *camera_mode = CameraMode::Follow;
+        }
+    }
+}
+
+/// Zoom with the mouse wheel, pan with the right mouse button or the
+/// movement keys while in map view.
+pub fn map_view_controls(
+    camera_mode: Res<CameraMode>,
+    action_q: Query<&ActionState<RLAction>, With<Player>>,
+    mut camera_q: Query<(&Camera, &mut Transform, &mut OrthographicProjection), With<MyGameCamera>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<Input<MouseButton>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut motion_events: EventReader<MouseMotion>,
+    map: Res<GameMap>,
+    time: Res<Time>,
+) {
+    if *camera_mode != CameraMode::MapView {
+        wheel_events.clear();
+        motion_events.clear();
+        return;
+    }
+    let (camera, mut transform, mut projection) = match camera_q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let viewport = match camera.logical_viewport_size() {
+        Some(viewport) => viewport,
+        None => return,
+    };
+
+    // the viewport is in the top left corner of the window, the wheel
+    // scrolls the side panel outside of it
+    let over_map = q_windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .map_or(false, |cursor| {
+            cursor.x < viewport.x && cursor.y < viewport.y
+        });
+    let mut zoom = 0.;
+    for event in wheel_events.iter() {
+        if over_map {
+            zoom += event.y.signum();
+        }
+    }
+    if zoom != 0. {
+        projection.scale =
+            (projection.scale * ZOOM_STEP.powf(-zoom)).clamp(MIN_SCALE, max_scale(&map, viewport));
+    }
+
+    // screen y goes down, world y goes up
+    let mut pan = Vec2::ZERO;
+    if buttons.pressed(MouseButton::Right) {
+        for event in motion_events.iter() {
+            pan += Vec2::new(-event.delta.x, event.delta.y);
+        }
+    } else {
+        motion_events.clear();
+    }
+    if let Ok(action) = action_q.get_single() {
+        let speed = PAN_SPEED * time.delta_seconds();
+        for (key, direction) in [
+            (RLAction::Up, Vec2::Y),
+            (RLAction::Down, Vec2::NEG_Y),
+            (RLAction::Left, Vec2::NEG_X),
+            (RLAction::Right, Vec2::X),
+        ] {
+            if action.pressed(key) {
+                pan += direction * speed;
+            }
+        }
+    }
+
+    let position = clamp_camera(
+        transform.translation.truncate() + pan * projection.scale,
+        viewport * projection.scale,
+        map.world_bounds(),
+    );
+    transform.translation = position.extend(transform.translation.z);
+}
+
+/// Moves the camera towards the player once they leave the dead zone, keeps
+/// the view inside the map and adds the screen shake on top.
+pub fn camera_follow(
+    player_q: Query<&Transform, (With<Player>, Without<MyGameCamera>)>,
+    map: Res<GameMap>,
+    camera_mode: Res<CameraMode>,
+    settings: Res<CameraSettings>,
+    mut shake_events: EventReader<ScreenShakeEvent>,
+    time: Res<Time>,
+    mut camera_q: Query<
+        (
+            &Camera,
+            &mut Transform,
+            &OrthographicProjection,
+            &mut CameraController,
+        ),
+        With<MyGameCamera>,
+    >,
+) {
+    let (camera, mut transform, projection, mut controller) = match camera_q.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for event in shake_events.iter() {
+        if settings.shake_enabled {
+            controller.trauma = (controller.trauma + event.trauma).min(1.);
+        }
+    }
+
+    if *camera_mode == CameraMode::MapView {
+        // going back to follow mode glides from where the map view was left
+        controller.focus = transform.translation.truncate();
+        controller.trauma = 0.;
+        return;
+    }
+    let player = match player_q.get_single() {
+        Ok(player_transform) => player_transform.translation.truncate(),
+        Err(_) => return,
+    };
+    let view_size = camera.logical_viewport_size().unwrap_or(Vec2::ONE) * projection.scale;
+
+    let offset = player - controller.focus;
+    if offset.length() > view_size.length() / 2. {
+        // far away (new level, teleport): no point in scrolling there
+        controller.focus = player;
+    } else {
+        let half_zone = settings.dead_zone / 2.;
+        let overshoot = offset - offset.clamp(-half_zone, half_zone);
+        let ratio = 1. - (-settings.smoothing * time.delta_seconds()).exp();
+        controller.focus += overshoot * ratio;
+    }
+    controller.focus = clamp_camera(controller.focus, view_size, map.world_bounds());
+
+    let mut shake = Vec2::ZERO;
+    if controller.trauma > 0. {
+        let mut rng = rand::thread_rng();
+        let amount = controller.trauma * controller.trauma * settings.max_shake;
+        shake = Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * amount;
+        controller.trauma =
+            (controller.trauma - settings.shake_decay * time.delta_seconds()).max(0.);
+    }
+    transform.translation = (controller.focus + shake).extend(transform.translation.z);
+}
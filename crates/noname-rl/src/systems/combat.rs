@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use crate::{
+    events::{DamageEvent, DeathEvent},
+    Attack, Defense, EffectiveStats, Equipment, Health, ItemTable, Monster, Player,
+};
+
+type StatsQueryData = (
+    Entity,
+    &'static mut Health,
+    &'static Attack,
+    &'static Defense,
+    Option<&'static Equipment>,
+    &'static mut EffectiveStats,
+);
+
+/// Recomputes `EffectiveStats` when the base stats or the equipment change.
+pub fn update_effective_stats(
+    mut stats_q: Query<
+        StatsQueryData,
+        Or<(
+            Changed<Equipment>,
+            Changed<Attack>,
+            Changed<Defense>,
+            Changed<Health>,
+            Added<EffectiveStats>,
+        )>,
+    >,
+    table: Res<ItemTable>,
+) {
+    for (entity, mut health, attack, defense, equipment, mut stats) in stats_q.iter_mut() {
+        let bonus = equipment.map(|e| e.bonus(&table)).unwrap_or_default();
+        let effective = EffectiveStats {
+            max_health: health.max + bonus.max_health,
+            attack: attack.damage + bonus.attack,
+            defense: defense.value + bonus.defense,
+        };
+        if *stats != effective {
+            info!("effective stats of {:?}: {:?}", entity, effective);
+            *stats = effective;
+        }
+        // taking off a ring of vitality lowers the max health
+        if health.current > effective.max_health {
+            health.current = effective.max_health;
+        }
+    }
+}
+
+/// Removes monsters whose health dropped to zero.
+pub fn update_deaths(
+    health_q: Query<(Entity, &Health, Option<&Monster>, Option<&Player>), Changed<Health>>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut commands: Commands,
+) {
+    let damage = damage_events.iter().copied().collect::<Vec<_>>();
+
+    for (entity, health, monster, player) in health_q.iter() {
+        if health.current > 0 {
+            continue;
+        }
+        let killer = damage
+            .iter()
+            .rev()
+            .find(|event| event.target == entity)
+            .and_then(|event| event.source);
+
+        if monster.is_some() {
+            info!("{:?} was killed by {:?}", entity, killer);
+            commands.entity(entity).despawn_recursive();
+            death_events.send(DeathEvent { entity, killer });
+        } else if player.is_some() {
+            info!("the player died (killed by {:?})", killer);
+            death_events.send(DeathEvent { entity, killer });
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use bevy::{
+    ecs::system::{Command, CommandQueue},
+    prelude::*,
+};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    occupied_tiles, resources::GameRng, spawn_item, DamageAction, GameMap, GameState, Health,
+    Invulnerable, Item, ItemTable, Monster, MonsterTable, MyAssets, NeedsFovUpdate, PickUpAction,
+    Player, RLAction, RevealMapAction, TeleportAction,
+};
+
+use super::monsters::spawn_monster_at;
+
+/// lines kept in the console output
+const CONSOLE_OUTPUT_LINES: usize = 200;
+
+/// commands of the debug console, with their usage
+pub const CONSOLE_COMMANDS: [(&str, &str); 10] = [
+    ("help", "help: lists the commands"),
+    ("teleport", "teleport <x> <y>: moves the player to the tile"),
+    (
+        "spawn",
+        "spawn <monster> <x> <y>: spawns a monster on the tile",
+    ),
+    ("reveal_map", "reveal_map: marks the whole map as visited"),
+    ("godmode", "godmode: the player takes no damage (toggles)"),
+    ("give", "give <item> [count]: puts an item in the inventory"),
+    ("set_seed", "set_seed <seed>: seeds the game rng"),
+    (
+        "regen_map",
+        "regen_map: builds a new dungeon from the current seed",
+    ),
+    ("kill_all", "kill_all: kills every monster"),
+    ("fov", "fov on|off: turns the field of view on or off"),
+];
+
+/// DebugConsole
+///
+/// In-game text console, opened with the ` key. The commands typed in are
+/// run by `run_console_commands`.
+#[derive(Resource, Debug, Default)]
+pub struct DebugConsole {
+    pub open: bool,
+    pub input: String,
+    pub output: Vec<String>,
+    history: Vec<String>,
+    /// entry of the history shown in the input, while browsing it
+    history_index: Option<usize>,
+    /// submitted lines not run yet
+    pending: Vec<String>,
+}
+
+impl DebugConsole {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+        if self.output.len() > CONSOLE_OUTPUT_LINES {
+            let overflow = self.output.len() - CONSOLE_OUTPUT_LINES;
+            self.output.drain(..overflow);
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = self.input.trim().to_string();
+        self.input.clear();
+        self.history_index = None;
+        if line.is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.pending.push(line);
+    }
+
+    /// shows the previous (`older`) or next entry of the history
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        self.input = index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+
+    /// completes the word being typed from `candidates`, listing them when
+    /// there is more than one
+    fn complete<'a>(&mut self, candidates: impl Iterator<Item = &'a str>) {
+        let split = self.input.rfind(' ').map_or(0, |index| index + 1);
+        let (head, word) = self.input.split_at(split);
+        let (head, word) = (head.to_string(), word.to_string());
+        let matches = candidates
+            .filter(|candidate| candidate.starts_with(&word))
+            .collect::<Vec<_>>();
+        let completed = match matches.as_slice() {
+            [] => return,
+            [single] => format!("{}{} ", head, single),
+            [first, others @ ..] => {
+                // the longest prefix shared by every candidate
+                let shared = others.iter().fold(first.len(), |len, other| {
+                    first
+                        .chars()
+                        .zip(other.chars())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                let listed = matches.join("  ");
+                self.print(listed);
+                format!("{}{}", head, &first[..shared])
+            }
+        };
+        self.input = completed;
+    }
+}
+
+/// DebugFlags
+///
+/// Cheats switched from the debug console.
+#[derive(Resource, Debug, Clone)]
+pub struct DebugFlags {
+    /// when off, the whole map is visible
+    pub fov: bool,
+}
+
+impl Default for DebugFlags {
+    fn default() -> Self {
+        Self { fov: true }
+    }
+}
+
+/// Opens and closes the console; the game ignores the keyboard while it is
+/// open.
+pub fn debug_console_toggle(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut console: ResMut<DebugConsole>,
+    mut toggle_actions: ResMut<ToggleActions<RLAction>>,
+) {
+    if keys.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+        toggle_actions.enabled = !console.open;
+    }
+    if console.open {
+        keys.reset_all();
+    }
+}
+
+pub fn debug_console_ui(
+    mut contexts: EguiContexts,
+    mut console: ResMut<DebugConsole>,
+    monsters: Res<MonsterTable>,
+    items: Res<ItemTable>,
+) {
+    if !console.open {
+        return;
+    }
+
+    egui::Window::new("console")
+        .default_size([480., 320.])
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(260.)
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in console.output.iter() {
+                        ui.monospace(line);
+                    }
+                });
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .lock_focus(true),
+            );
+            // the toggle key and tab are typed in too
+            console.input.retain(|c| c != '`' && c != '\t');
+
+            let (enter, tab, up, down) = ui.input(|input| {
+                (
+                    input.key_pressed(egui::Key::Enter),
+                    input.key_pressed(egui::Key::Tab),
+                    input.key_pressed(egui::Key::ArrowUp),
+                    input.key_pressed(egui::Key::ArrowDown),
+                )
+            });
+            if enter {
+                console.submit();
+            } else if tab {
+                let words = console.input.split_whitespace().count();
+                let new_word = console.input.is_empty() || console.input.ends_with(' ');
+                let argument = words > 1 || (words == 1 && new_word);
+                if !argument {
+                    console.complete(CONSOLE_COMMANDS.iter().map(|(name, _)| *name));
+                } else {
+                    let command = console.input.split_whitespace().next().unwrap_or_default();
+                    match command.to_owned().as_str() {
+                        "spawn" => console.complete(monsters.iter().map(|m| m.id.as_str())),
+                        "give" => console.complete(items.iter().map(|i| i.id.as_str())),
+                        "fov" => console.complete(["on", "off"].into_iter()),
+                        _ => {}
+                    }
+                }
+            } else if up {
+                console.browse_history(true);
+            } else if down {
+                console.browse_history(false);
+            }
+            response.request_focus();
+        });
+}
+
+/// Runs the commands submitted to the console.
+pub fn run_console_commands(world: &mut World) {
+    let lines = std::mem::take(&mut world.resource_mut::<DebugConsole>().pending);
+    for line in lines {
+        let output = run_console_command(world, &line);
+        let mut console = world.resource_mut::<DebugConsole>();
+        console.print(format!("> {}", line));
+        match output {
+            Ok(text) => console.print(text),
+            Err(error) => console.print(format!("error: {}", error)),
+        }
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let arg = args.get(index).ok_or(format!("missing {}", name))?;
+    arg.parse::<T>()
+        .map_err(|_| format!("invalid {} '{}'", name, arg))
+}
+
+fn parse_tile(world: &World, args: &[&str], index: usize) -> Result<TilePos, String> {
+    let x = parse_arg::<i32>(args, index, "x")?;
+    let y = parse_arg::<i32>(args, index + 1, "y")?;
+    world
+        .resource::<GameMap>()
+        .checked_pos(IVec2::new(x, y))
+        .ok_or(format!("{} {} is outside the map", x, y))
+}
+
+fn run_console_command(world: &mut World, line: &str) -> Result<String, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(String::new()),
+    };
+    if command == "help" {
+        let usage = CONSOLE_COMMANDS.iter().map(|(_, usage)| *usage);
+        return Ok(usage.collect::<Vec<_>>().join("\n"));
+    }
+    if !world.contains_resource::<GameMap>() {
+        return Err("no game is running".to_string());
+    }
+    let player = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .map_err(|_| "no player".to_string())?;
+
+    match command {
+        "teleport" => {
+            let target = parse_tile(world, args, 0)?;
+            if world.resource::<GameMap>().is_blocked(&target) {
+                return Err(format!("{} {} is blocked", target.x, target.y));
+            }
+            if occupied_tiles(world).contains(&target) {
+                return Err(format!("{} {} is occupied", target.x, target.y));
+            }
+            TeleportAction {
+                entity: player,
+                target: Some(target),
+            }
+            .apply(world);
+            Ok(format!("teleported to {} {}", target.x, target.y))
+        }
+        "spawn" => {
+            let id = args.first().ok_or("missing monster")?;
+            let definition = world
+                .resource::<MonsterTable>()
+                .get(id)
+                .cloned()
+                .ok_or(format!("unknown monster '{}'", id))?;
+            let tile_pos = parse_tile(world, args, 1)?;
+            let mut queue = CommandQueue::default();
+            {
+                let mut commands = Commands::new(&mut queue, world);
+                let assets = world.resource::<MyAssets>();
+                let map = world.resource::<GameMap>();
+                spawn_monster_at(&mut commands, assets, map, &definition, tile_pos);
+            }
+            queue.apply(world);
+            Ok(format!("spawned {} at {} {}", id, tile_pos.x, tile_pos.y))
+        }
+        "reveal_map" => {
+            RevealMapAction.apply(world);
+            Ok("map revealed".to_string())
+        }
+        "godmode" => {
+            let enabled = world.get::<Invulnerable>(player).is_none();
+            if enabled {
+                world.entity_mut(player).insert(Invulnerable);
+            } else {
+                world.entity_mut(player).remove::<Invulnerable>();
+            }
+            Ok(format!("godmode {}", if enabled { "on" } else { "off" }))
+        }
+        "give" => {
+            let id = args.first().ok_or("missing item")?;
+            if world.resource::<ItemTable>().get(id).is_none() {
+                return Err(format!("unknown item '{}'", id));
+            }
+            let count = match args.get(1) {
+                Some(_) => parse_arg::<u32>(args, 1, "count")?,
+                None => 1,
+            };
+            // dropped at the player's feet and picked up, as in the game
+            let tile_pos = *world.get::<TilePos>(player).ok_or("no player position")?;
+            let item = spawn_item(world, Item::new(id, count), tile_pos)
+                .ok_or(format!("can't spawn '{}'", id))?;
+            PickUpAction {
+                entity: player,
+                item,
+            }
+            .apply(world);
+            Ok(format!("gave {} {}", count, id))
+        }
+        "set_seed" => {
+            let seed = parse_arg::<u64>(args, 0, "seed")?;
+            world.insert_resource(GameRng::new(seed));
+            Ok(format!("seed set to {}, regen_map to use it", seed))
+        }
+        "regen_map" => {
+            // the same seed builds the same dungeon, from its first roll
+            let seed = world.resource::<GameRng>().seed;
+            world.insert_resource(GameRng::new(seed));
+            world
+                .resource_mut::<NextState<GameState>>()
+                .set(GameState::AssetsLoaded);
+            Ok(format!("building the dungeon of seed {}", seed))
+        }
+        "kill_all" => {
+            let monsters = world
+                .query_filtered::<(Entity, &Health), With<Monster>>()
+                .iter(world)
+                .filter(|(_, health)| health.current > 0)
+                .map(|(entity, health)| (entity, health.current))
+                .collect::<Vec<_>>();
+            let killed = monsters.len();
+            for (entity, amount) in monsters {
+                DamageAction {
+                    entity,
+                    amount,
+                    source: Some(player),
+                }
+                .apply(world);
+            }
+            Ok(format!("killed {} monsters", killed))
+        }
+        "fov" => {
+            let fov = match args.first() {
+                Some(&"on") => true,
+                Some(&"off") => false,
+                _ => return Err("usage: fov on|off".to_string()),
+            };
+            world.resource_mut::<DebugFlags>().fov = fov;
+            world.resource_mut::<GameMap>().set_all_visible(!fov);
+            if fov {
+                world.entity_mut(player).insert(NeedsFovUpdate);
+            }
+            Ok(format!("fov {}", args[0]))
+        }
+        _ => Err(format!("unknown command '{}', see help", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console_with(input: &str) -> DebugConsole {
+        DebugConsole {
+            input: input.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn complete_single_match() {
+        let mut console = console_with("tele");
+        console.complete(CONSOLE_COMMANDS.iter().map(|(name, _)| *name));
+        assert_eq!(console.input, "teleport ");
+    }
+
+    #[test]
+    fn complete_shared_prefix() {
+        let candidates = ["health_potion", "healing_potion", "bomb"];
+        let mut console = console_with("give he");
+        console.complete(candidates.into_iter());
+        assert_eq!(console.input, "give heal");
+        assert_eq!(
+            console.output.last().map(String::as_str),
+            Some("health_potion  healing_potion")
+        );
+
+        // nothing to complete from
+        let mut console = console_with("give x");
+        console.complete(candidates.into_iter());
+        assert_eq!(console.input, "give x");
+        assert!(console.output.is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+use bevy_prototype_debug_lines::DebugLines;
+
+use crate::{
+    AiStateLabel, GameMap, Health, HoveredTile, Item, Monster, MyAssets, Player, StatusEffects,
+    StatusKind, Travel,
+};
+
+use super::setup::DungeonLayout;
+
+/// walking cost covered by the heatmap around the player
+const HEATMAP_MAX_COST: u32 = 24;
+
+/// DebugOverlays
+///
+/// Debug drawings over the map, switched from the panel opened with F3.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct DebugOverlays {
+    pub open: bool,
+    pub fov: bool,
+    pub paths: bool,
+    pub heatmap: bool,
+    pub ai_state: bool,
+    pub rooms: bool,
+    pub occupancy: bool,
+}
+
+/// FovDebug
+///
+/// Rays cast by the last `update_visibile_tiles`: each one ends on its last
+/// tile, the occluder that stopped it or the edge of the view.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct FovDebug {
+    pub origin: TilePos,
+    pub rays: Vec<TilePos>,
+    pub occluders: HashSet<TilePos>,
+}
+
+fn tile_center(map: &GameMap, tile_pos: &TilePos) -> Vec3 {
+    map.tile_to_world(tile_pos).extend(1.)
+}
+
+/// bottom left corner of a cell, which may lie outside the map
+fn cell_corner(map: &GameMap, cell: IVec2) -> Vec3 {
+    let grid_size = Vec2::new(map.grid_size.x, map.grid_size.y);
+    let origin = map.tile_to_world(&TilePos::new(0, 0)) - grid_size / 2.;
+    (origin + cell.as_vec2() * grid_size).extend(1.)
+}
+
+fn draw_cell_rect(lines: &mut DebugLines, map: &GameMap, min: IVec2, max: IVec2, color: Color) {
+    let corners = [
+        cell_corner(map, min),
+        cell_corner(map, IVec2::new(max.x, min.y)),
+        cell_corner(map, max),
+        cell_corner(map, IVec2::new(min.x, max.y)),
+    ];
+    for i in 0..corners.len() {
+        lines.line_colored(corners[i], corners[(i + 1) % corners.len()], 0., color);
+    }
+}
+
+fn draw_tile_rect(lines: &mut DebugLines, map: &GameMap, tile_pos: &TilePos, color: Color) {
+    let cell = IVec2::new(tile_pos.x as i32, tile_pos.y as i32);
+    draw_cell_rect(lines, map, cell, cell + IVec2::ONE, color);
+}
+
+fn draw_tile_cross(lines: &mut DebugLines, map: &GameMap, tile_pos: &TilePos, color: Color) {
+    let cell = IVec2::new(tile_pos.x as i32, tile_pos.y as i32);
+    let (min, max) = (cell_corner(map, cell), cell_corner(map, cell + IVec2::ONE));
+    lines.line_colored(min, max, 0., color);
+    lines.line_colored(
+        Vec3::new(min.x, max.y, 1.),
+        Vec3::new(max.x, min.y, 1.),
+        0.,
+        color,
+    );
+}
+
+fn draw_path(lines: &mut DebugLines, map: &GameMap, path: &[TilePos], color: Color) {
+    for step in path.windows(2) {
+        lines.line_colored(
+            tile_center(map, &step[0]),
+            tile_center(map, &step[1]),
+            0.,
+            color,
+        );
+    }
+}
+
+pub fn debug_overlay_panel(
+    keys: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut overlays: ResMut<DebugOverlays>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        overlays.open = !overlays.open;
+    }
+    if !overlays.open {
+        return;
+    }
+
+    egui::Window::new("debug overlays")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut overlays.fov, "fov rays and occluders");
+            ui.checkbox(&mut overlays.paths, "a* paths (cursor, travel)");
+            ui.checkbox(&mut overlays.heatmap, "dijkstra heatmap from the player");
+            ui.checkbox(&mut overlays.ai_state, "monster ai state");
+            ui.checkbox(&mut overlays.rooms, "rooms and corridors");
+            ui.checkbox(&mut overlays.occupancy, "occupancy grid");
+        });
+}
+
+pub fn draw_fov_overlay(
+    overlays: Res<DebugOverlays>,
+    map: Res<GameMap>,
+    fov_debug: Res<FovDebug>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !overlays.fov {
+        return;
+    }
+    let origin = tile_center(&map, &fov_debug.origin);
+    for ray_end in fov_debug.rays.iter() {
+        let color = if fov_debug.occluders.contains(ray_end) {
+            Color::ORANGE
+        } else {
+            Color::YELLOW
+        };
+        lines.line_colored(origin, tile_center(&map, ray_end), 0., color);
+    }
+    for occluder in fov_debug.occluders.iter() {
+        draw_tile_rect(&mut lines, &map, occluder, Color::RED);
+    }
+}
+
+/// The path the player would take to the hovered tile, and the one being
+/// travelled.
+pub fn draw_path_overlay(
+    overlays: Res<DebugOverlays>,
+    map: Res<GameMap>,
+    hovered: Res<HoveredTile>,
+    travel: Res<Travel>,
+    player_q: Query<&TilePos, With<Player>>,
+    mut lines: ResMut<DebugLines>,
+    // searching the whole map every frame is too slow when the goal can't
+    // be reached
+    mut cached: Local<(Option<(TilePos, TilePos)>, Vec<TilePos>)>,
+) {
+    if !overlays.paths {
+        return;
+    }
+    let player_pos = match player_q.get_single() {
+        Ok(player_pos) => *player_pos,
+        Err(_) => return,
+    };
+
+    if let Some(goal) = hovered.tile {
+        if cached.0 != Some((player_pos, goal)) {
+            let path = map.find_path(player_pos, goal).unwrap_or_default();
+            *cached = (Some((player_pos, goal)), path);
+        }
+        draw_path(&mut lines, &map, &cached.1, Color::CYAN);
+    }
+
+    let travel_path = std::iter::once(player_pos)
+        .chain(travel.path().copied())
+        .collect::<Vec<_>>();
+    draw_path(&mut lines, &map, &travel_path, Color::FUCHSIA);
+}
+
+/// Walking cost from the player, green when close and red when far.
+pub fn draw_heatmap_overlay(
+    overlays: Res<DebugOverlays>,
+    map: Res<GameMap>,
+    player_q: Query<&TilePos, With<Player>>,
+    mut lines: ResMut<DebugLines>,
+    mut cached: Local<Option<(TilePos, HashMap<TilePos, u32>)>>,
+) {
+    if !overlays.heatmap {
+        // the map may change while the overlay is off
+        *cached = None;
+        return;
+    }
+    let player_pos = match player_q.get_single() {
+        Ok(player_pos) => *player_pos,
+        Err(_) => return,
+    };
+
+    // doors, undo and new maps change the costs under a standing player
+    if map.is_changed() || cached.as_ref().map(|(start, _)| *start) != Some(player_pos) {
+        *cached = Some((player_pos, map.distance_map(player_pos, HEATMAP_MAX_COST)));
+    }
+    let distances = match cached.as_ref() {
+        Some((_, distances)) => distances,
+        None => return,
+    };
+    for (tile_pos, cost) in distances.iter() {
+        let t = *cost as f32 / HEATMAP_MAX_COST as f32;
+        draw_tile_cross(&mut lines, &map, tile_pos, Color::rgb(t, 1. - t, 0.));
+    }
+}
+
+pub fn draw_room_overlay(
+    overlays: Res<DebugOverlays>,
+    map: Res<GameMap>,
+    layout: Res<DungeonLayout>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !overlays.rooms {
+        return;
+    }
+    for room in layout.rooms.iter() {
+        draw_cell_rect(
+            &mut lines,
+            &map,
+            room.pos(),
+            room.pos() + room.size(),
+            Color::GREEN,
+        );
+    }
+    let half_cell = Vec3::new(map.grid_size.x, map.grid_size.y, 0.) / 2.;
+    for (start, end) in layout.corridors.iter() {
+        lines.line_colored(
+            cell_corner(&map, *start) + half_cell,
+            cell_corner(&map, *end) + half_cell,
+            0.,
+            Color::LIME_GREEN,
+        );
+    }
+}
+
+/// Tiles holding actors (red), items (blue) or both (white).
+pub fn draw_occupancy_overlay(
+    overlays: Res<DebugOverlays>,
+    map: Res<GameMap>,
+    actors_q: Query<&TilePos, With<Health>>,
+    items_q: Query<&TilePos, With<Item>>,
+    mut lines: ResMut<DebugLines>,
+) {
+    if !overlays.occupancy {
+        return;
+    }
+    let mut occupancy: HashMap<TilePos, (u32, u32)> = HashMap::new();
+    for tile_pos in actors_q.iter() {
+        occupancy.entry(*tile_pos).or_default().0 += 1;
+    }
+    for tile_pos in items_q.iter() {
+        occupancy.entry(*tile_pos).or_default().1 += 1;
+    }
+    for (tile_pos, (actors, items)) in occupancy.iter() {
+        let color = match (*actors, *items) {
+            (actors, 0) if actors > 0 => Color::RED,
+            (0, _) => Color::BLUE,
+            _ => Color::WHITE,
+        };
+        draw_tile_rect(&mut lines, &map, tile_pos, color);
+    }
+}
+
+/// monsters hunt the player in sight (see `update_enemies`), and idle
+/// otherwise
+fn ai_state(map: &GameMap, tile_pos: &TilePos, effects: &StatusEffects) -> &'static str {
+    if effects.has(StatusKind::Stunned) {
+        "stunned"
+    } else if effects.has(StatusKind::Confused) {
+        "confused"
+    } else if map.is_visible(tile_pos) {
+        "hunting"
+    } else {
+        "idle"
+    }
+}
+
+/// Shows the state of every monster above it.
+pub fn update_ai_state_labels(
+    overlays: Res<DebugOverlays>,
+    assets: Res<MyAssets>,
+    map: Res<GameMap>,
+    monsters_q: Query<(Entity, &TilePos, &StatusEffects), With<Monster>>,
+    mut labels_q: Query<(Entity, &Parent, &mut Text), With<AiStateLabel>>,
+    mut commands: Commands,
+) {
+    if !overlays.ai_state {
+        for (label, _, _) in labels_q.iter() {
+            commands.entity(label).despawn_recursive();
+        }
+        return;
+    }
+
+    let mut labelled = HashSet::new();
+    for (_, parent, mut text) in labels_q.iter_mut() {
+        if let Ok((monster, tile_pos, effects)) = monsters_q.get(parent.get()) {
+            labelled.insert(monster);
+            let state = ai_state(&map, tile_pos, effects);
+            if text.sections[0].value != state {
+                text.sections[0].value = state.to_string();
+            }
+        }
+    }
+
+    for (monster, tile_pos, effects) in monsters_q.iter() {
+        if labelled.contains(&monster) {
+            continue;
+        }
+        let label = commands
+            .spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        ai_state(&map, tile_pos, effects),
+                        TextStyle {
+                            font: assets.ui_font.clone(),
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., 12., 1.).with_scale(Vec3::splat(0.5)),
+                    ..Default::default()
+                },
+                Name::new("AiStateLabel"),
+                AiStateLabel,
+            ))
+            .id();
+        commands.entity(monster).add_child(label);
+    }
+}
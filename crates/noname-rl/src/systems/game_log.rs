@@ -0,0 +1,138 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use crate::{
+    describe,
+    events::{DamageEvent, DeathEvent, LevelUpEvent, StatusAppliedEvent, StatusExpiredEvent},
+    resources::RLTimeSystem,
+    GameLog, GameLogPanel, GameLogUI, LogCategory, Player,
+};
+
+/// entries shown at once in the log panel
+pub const GAME_LOG_LINES: usize = 12;
+
+/// picks the verb form agreeing with `subject` ("you take", "the rat takes")
+fn verb<'a>(subject: &str, you: &'a str, other: &'a str) -> &'a str {
+    if subject == "you" {
+        you
+    } else {
+        other
+    }
+}
+
+/// Writes the gameplay events of this frame to the `GameLog`. Runs before
+/// the dead monsters are despawned so their names can still be read.
+pub fn log_gameplay_events(
+    mut log: ResMut<GameLog>,
+    time_system: Res<RLTimeSystem>,
+    names_q: Query<(Option<&Name>, Option<&Player>)>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut status_applied_events: EventReader<StatusAppliedEvent>,
+    mut status_expired_events: EventReader<StatusExpiredEvent>,
+    mut level_up_events: EventReader<LevelUpEvent>,
+) {
+    let turn = time_system.get_time();
+    let name = |entity: Entity| match names_q.get(entity) {
+        Ok((name, player)) => describe(name, player.is_some()),
+        Err(_) => "something".to_string(),
+    };
+
+    for event in damage_events.iter() {
+        let target = name(event.target);
+        let text = format!(
+            "{} {} {} damage",
+            target,
+            verb(&target, "take", "takes"),
+            event.amount
+        );
+        log.push(turn, LogCategory::Damage, text);
+    }
+    for event in death_events.iter() {
+        let text = match event.killer {
+            Some(killer) => format!("{} killed {}", name(killer), name(event.entity)),
+            None => format!("{} died", name(event.entity)),
+        };
+        log.push(turn, LogCategory::Death, text);
+    }
+    for event in status_applied_events.iter() {
+        let entity = name(event.entity);
+        let text = format!("{} {} {:?}", entity, verb(&entity, "are", "is"), event.kind);
+        log.push(turn, LogCategory::Status, text.to_lowercase());
+    }
+    for event in status_expired_events.iter() {
+        let entity = name(event.entity);
+        let text = format!(
+            "{} {} no longer {:?}",
+            entity,
+            verb(&entity, "are", "is"),
+            event.kind
+        );
+        log.push(turn, LogCategory::Status, text.to_lowercase());
+    }
+    for event in level_up_events.iter() {
+        log.push(
+            turn,
+            LogCategory::Progress,
+            format!("{} reached level {}", name(event.entity), event.level),
+        );
+    }
+}
+
+/// Rebuilds the log panel text when the log changes.
+pub fn game_ui_log_update(log: Res<GameLog>, mut log_ui: Query<&mut Text, With<GameLogUI>>) {
+    if !log.is_changed() {
+        return;
+    }
+    let mut text = match log_ui.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let style = match text.sections.first() {
+        Some(section) => section.style.clone(),
+        None => return,
+    };
+
+    text.sections = log
+        .visible(GAME_LOG_LINES)
+        .map(|entry| {
+            TextSection::new(
+                format!("[{}] {}\n", entry.turn, entry.text),
+                TextStyle {
+                    color: entry.category.color(),
+                    ..style.clone()
+                },
+            )
+        })
+        .collect();
+    if text.sections.is_empty() {
+        text.sections.push(TextSection::new("", style));
+    }
+}
+
+/// Scrolls the log with the mouse wheel (over the panel) or PageUp/PageDown.
+pub fn game_ui_log_scroll(
+    mut log: ResMut<GameLog>,
+    mut wheel_events: EventReader<MouseWheel>,
+    panel_q: Query<&Interaction, With<GameLogPanel>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    let hovered = panel_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let mut lines = 0.;
+    for event in wheel_events.iter() {
+        if hovered {
+            lines += event.y.signum();
+        }
+    }
+    if keys.just_pressed(KeyCode::PageUp) {
+        lines += GAME_LOG_LINES as f32 / 2.;
+    }
+    if keys.just_pressed(KeyCode::PageDown) {
+        lines -= GAME_LOG_LINES as f32 / 2.;
+    }
+
+    if lines != 0. {
+        log.scroll_by(lines as i32);
+    }
+}
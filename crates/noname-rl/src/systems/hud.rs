@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use crate::{
+    resources::{Dungeon, RLTimeSystem},
+    EffectiveStats, Experience, Health, HudElement, MyAssets, Player, StatusEffects,
+};
+
+/// width of the health bar in the side panel
+const HEALTH_BAR_WIDTH: f32 = 200.;
+
+/// Spawns the player status HUD at the top of the side panel.
+pub fn spawn_hud(builder: &mut ChildBuilder, assets: &MyAssets) {
+    let text_color = Color::hex("fcfcfc").unwrap();
+    let data_color = Color::hex("f7d8bc").unwrap();
+    let text = |size: f32, color: Color| TextStyle {
+        font: assets.ui_font.clone(),
+        font_size: size,
+        color,
+    };
+
+    builder.spawn((
+        TextBundle::from_section("", text(28.0, text_color)),
+        HudElement::HealthText,
+    ));
+    builder
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(HEALTH_BAR_WIDTH),
+                height: Val::Px(12.),
+                margin: UiRect::vertical(Val::Px(4.)),
+                ..Default::default()
+            },
+            background_color: BackgroundColor(Color::hex("3a2a2aff").unwrap()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        ..Default::default()
+                    },
+                    background_color: BackgroundColor(Color::GREEN),
+                    ..Default::default()
+                },
+                HudElement::HealthBar,
+            ));
+        });
+    for element in [
+        HudElement::Stats,
+        HudElement::Level,
+        HudElement::DepthAndTurn,
+        HudElement::Statuses,
+    ] {
+        builder.spawn((
+            TextBundle::from_section("", text(22.0, data_color)),
+            element,
+        ));
+    }
+}
+
+fn health_color(ratio: f32) -> Color {
+    if ratio > 0.5 {
+        Color::hex("4caf50ff").unwrap()
+    } else if ratio > 0.25 {
+        Color::hex("e0b040ff").unwrap()
+    } else {
+        Color::hex("d04040ff").unwrap()
+    }
+}
+
+pub fn hud_health_update(
+    player_q: Query<
+        (&Health, &EffectiveStats),
+        (With<Player>, Or<(Changed<Health>, Changed<EffectiveStats>)>),
+    >,
+    mut texts_q: Query<(&mut Text, &HudElement)>,
+    mut bars_q: Query<(&mut Style, &mut BackgroundColor, &HudElement), Without<Text>>,
+) {
+    let (health, stats) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let ratio = (health.current.max(0) as f32 / stats.max_health.max(1) as f32).min(1.);
+
+    for (mut text, element) in texts_q.iter_mut() {
+        if *element == HudElement::HealthText {
+            text.sections[0].value = format!("health {}/{}", health.current, stats.max_health);
+        }
+    }
+    for (mut style, mut color, element) in bars_q.iter_mut() {
+        if *element == HudElement::HealthBar {
+            style.width = Val::Percent(ratio * 100.);
+            color.0 = health_color(ratio);
+        }
+    }
+}
+
+pub fn hud_stats_update(
+    player_q: Query<
+        (&EffectiveStats, &Experience),
+        (
+            With<Player>,
+            Or<(Changed<EffectiveStats>, Changed<Experience>)>,
+        ),
+    >,
+    mut texts_q: Query<(&mut Text, &HudElement)>,
+) {
+    let (stats, experience) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for (mut text, element) in texts_q.iter_mut() {
+        match element {
+            HudElement::Stats => {
+                text.sections[0].value =
+                    format!("attack {}  defense {}", stats.attack, stats.defense);
+            }
+            HudElement::Level => {
+                text.sections[0].value = format!(
+                    "level {}  xp {}/{}",
+                    experience.level,
+                    experience.xp,
+                    experience.next_threshold()
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Depth, turn counter and statuses (with the time they have left) change
+/// with the game time.
+pub fn hud_turn_update(
+    time_system: Res<RLTimeSystem>,
+    dungeon: Res<Dungeon>,
+    player_q: Query<Ref<StatusEffects>, With<Player>>,
+    mut texts_q: Query<(&mut Text, &HudElement)>,
+) {
+    let effects = match player_q.get_single() {
+        Ok(effects) => effects,
+        Err(_) => return,
+    };
+    if !time_system.is_changed() && !dungeon.is_changed() && !effects.is_changed() {
+        return;
+    }
+
+    let now = time_system.get_time();
+    let statuses = effects
+        .effects
+        .iter()
+        .map(|effect| {
+            let kind = format!("{:?}", effect.kind).to_lowercase();
+            format!("{} ({})", kind, effect.expires_at.saturating_sub(now))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    for (mut text, element) in texts_q.iter_mut() {
+        match element {
+            HudElement::DepthAndTurn => {
+                text.sections[0].value = format!("depth {}  turn {}", dungeon.depth, now);
+            }
+            HudElement::Statuses => text.sections[0].value = statuses.clone(),
+            _ => {}
+        }
+    }
+}
@@ -23,4 +23,28 @@ pub fn setup_input_handler(
 
     input_map.insert(KeyCode::Right, Right);
     input_map.insert(GamepadButtonType::DPadRight, Right);
+
+    input_map.insert(KeyCode::O, OpenDoor);
+    input_map.insert(KeyCode::C, CloseDoor);
+
+    input_map.insert(KeyCode::G, PickUp);
+    input_map.insert(KeyCode::D, Drop);
+    input_map.insert(KeyCode::Tab, NextItem);
+    input_map.insert(KeyCode::U, UseItem);
+    input_map.insert(KeyCode::E, Equip);
+    input_map.insert(KeyCode::R, Unequip);
+    input_map.insert(KeyCode::F, Fire);
+    input_map.insert(KeyCode::Tab, NextTarget);
+
+    input_map.insert(KeyCode::Return, Confirm);
+    input_map.insert(KeyCode::Back, Cancel);
+
+    input_map.insert(KeyCode::M, ToggleMinimap);
+    input_map.insert(KeyCode::V, MapView);
+    input_map.insert(KeyCode::F1, Options);
+    input_map.insert(KeyCode::P, Pause);
+    // only in debug builds, see `UndoHistory`
+    if cfg!(debug_assertions) {
+        input_map.insert(KeyCode::Z, Undo);
+    }
 }
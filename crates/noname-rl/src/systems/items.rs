@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ecs_tilemap::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::*};
+
+use crate::{
+    resources::GameRng, spawn_item, DoorState, GameMap, Item, ItemTable, Player, TileKind,
+};
+
+/// tiles reachable from `start` without going through locked doors, in the
+/// order they are reached so a seed always gives the same picks
+fn reachable_tiles(map: &GameMap, start: TilePos) -> Vec<TilePos> {
+    let mut reached = HashSet::new();
+    let mut order = vec![start];
+    let mut queue = VecDeque::from([start]);
+    reached.insert(start);
+
+    while let Some(pos) = queue.pop_front() {
+        let cell = IVec2::new(pos.x as i32, pos.y as i32);
+        for d in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            if let Some(next) = map.checked_pos(cell + d) {
+                if map.path_cost(&next).is_some() && reached.insert(next) {
+                    order.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    order
+}
+
+pub fn spawn_items(
+    mut commands: Commands,
+    map: Res<GameMap>,
+    table: Res<ItemTable>,
+    player_q: Query<&TilePos, With<Player>>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    let rng = &mut game_rng.rng;
+    let floor_tiles: Vec<TilePos> = map.tiles_of_kind(TileKind::Floor);
+
+    let definitions = table
+        .iter()
+        .filter(|item| item.spawn_weight > 0)
+        .collect::<Vec<_>>();
+    let weights = WeightedIndex::new(definitions.iter().map(|item| item.spawn_weight))
+        .expect("no item can be spawned");
+
+    let n_items = 60;
+
+    for _ in 0..n_items {
+        let definition = definitions[weights.sample(rng)];
+        let tile_pos = floor_tiles[rng.gen_range(0..floor_tiles.len())];
+        // currencies come in piles
+        let count = if definition.max_stack >= 100 {
+            rng.gen_range(1..=50)
+        } else {
+            1
+        };
+        let item = Item::new(&definition.id, count);
+
+        commands.add(move |world: &mut World| {
+            spawn_item(world, item, tile_pos);
+        });
+    }
+
+    // each key must be reachable without opening the doors it unlocks
+    let keys = locked_door_keys(&map);
+    if keys.is_empty() {
+        return;
+    }
+    let player_pos = match player_q.get_single() {
+        Ok(player_pos) => *player_pos,
+        Err(_) => return,
+    };
+    let reachable = reachable_tiles(&map, player_pos)
+        .into_iter()
+        .filter(|pos| map.kind(pos) == TileKind::Floor)
+        .collect::<Vec<_>>();
+
+    for key in keys {
+        let definition = match table.key_item(key) {
+            Some(definition) => definition,
+            None => {
+                warn!("no item opens the doors locked with key {}", key);
+                continue;
+            }
+        };
+        if let Some(tile_pos) = reachable.choose(rng).copied() {
+            let item = Item::new(&definition.id, 1);
+            commands.add(move |world: &mut World| {
+                spawn_item(world, item, tile_pos);
+            });
+        }
+    }
+}
+
+/// key ids used by the locked doors of the map, sorted
+fn locked_door_keys(map: &GameMap) -> Vec<u32> {
+    let mut keys = Vec::new();
+    for x in 0..map.size.x {
+        for y in 0..map.size.y {
+            if let Some(DoorState::Locked { key }) = map.door(&TilePos::new(x, y)) {
+                keys.push(key);
+            }
+        }
+    }
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
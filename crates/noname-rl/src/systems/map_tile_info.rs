@@ -2,74 +2,82 @@ use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_ecs_tilemap::prelude::*;
 use bevy_prototype_debug_lines::DebugLines;
 
-use crate::{
-    algorithms::tile_pos_to_world_pos, events::TileInfoEvent, MyGameCamera, TileMapLayer0,
-};
+use crate::{GameMap, MyGameCamera};
+
+/// Tile under the mouse cursor, if the cursor is inside the window and over the map.
+pub fn cursor_tile_pos(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    map: &GameMap,
+) -> Option<TilePos> {
+    let position = window.cursor_position()?;
+    let world_position = camera
+        .viewport_to_world(camera_transform, position)
+        .map(|ray| ray.origin.truncate())?;
+    map.world_to_tile(&world_position)
+}
+
+/// Draws the outline of a tile for the current frame.
+pub fn draw_tile_outline(lines: &mut DebugLines, map: &GameMap, tile_pos: &TilePos, color: Color) {
+    let grid_size = &map.grid_size;
+    let world_position = map.tile_to_world(tile_pos);
+
+    let border_pos = Vec3::new(world_position.x, world_position.y, 1.)
+        - Vec3::new(0.5 * grid_size.x, 0.5 * grid_size.y, 0.);
+
+    lines.line_colored(
+        Vec3::new(border_pos.x, border_pos.y, 1.),
+        Vec3::new(border_pos.x + grid_size.x, border_pos.y, 1.),
+        0.9,
+        color,
+    );
+    lines.line_colored(
+        Vec3::new(border_pos.x, border_pos.y, 1.),
+        Vec3::new(border_pos.x, border_pos.y + grid_size.y, 1.),
+        0.9,
+        color,
+    );
+    lines.line_colored(
+        Vec3::new(border_pos.x + grid_size.x, border_pos.y, 1.),
+        Vec3::new(border_pos.x + grid_size.x, border_pos.y + grid_size.y, 1.),
+        0.9,
+        color,
+    );
+    lines.line_colored(
+        Vec3::new(border_pos.x, border_pos.y + grid_size.y, 1.),
+        Vec3::new(border_pos.x + grid_size.x, border_pos.y + grid_size.y, 1.),
+        0.9,
+        color,
+    );
+}
+
+/// Tile currently under the mouse cursor, kept up to date by `my_cursor_system`.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct HoveredTile {
+    pub tile: Option<TilePos>,
+}
 
 pub fn my_cursor_system(
-    buttons: Res<Input<MouseButton>>,
     // query to get camera transform
     camera_q: Query<(&Camera, &GlobalTransform), With<MyGameCamera>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
-    mut tiles_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType), With<TileMapLayer0>>,
+    map: Res<GameMap>,
+    mut hovered: ResMut<HoveredTile>,
     mut lines: ResMut<DebugLines>,
-    mut tile_info_event: EventWriter<TileInfoEvent>,
 ) {
-    // get the camera info and transform
-    // assuming there is exactly one main camera entity, so query::single() is OK
-    let (camera, camera_transform) = camera_q.single();
-
-    let (map_size, grid_size, map_type) = tiles_q.single_mut();
-
-    if buttons.pressed(MouseButton::Right) {
-        // Right Button is being held down
-
-        // Games typically only have one window (the primary window)
-        if let Some(position) = q_windows.single().cursor_position() {
-            println!("Cursor is inside the primary window, at {:?}", position);
-            let world_position = camera
-                .viewport_to_world(camera_transform, position)
-                .map(|ray| ray.origin.truncate())
-                .unwrap();
-            let p =
-                TilePos::from_world_pos(&world_position, map_size, grid_size, map_type).unwrap();
-            let world_position = tile_pos_to_world_pos(&p, map_size, grid_size, map_type).unwrap();
-
-            let border_pos = Vec3::new(world_position.x as f32, world_position.y as f32, 1.)
-                - Vec3::new(0.5 * grid_size.x, 0.5 * grid_size.y, 0.);
-
-            lines.line_colored(
-                Vec3::new(border_pos.x, border_pos.y, 1.),
-                Vec3::new(border_pos.x + grid_size.x, border_pos.y, 1.),
-                0.9,
-                Color::GREEN,
-            );
-            lines.line_colored(
-                Vec3::new(border_pos.x, border_pos.y, 1.),
-                Vec3::new(border_pos.x, border_pos.y + grid_size.y, 1.),
-                0.9,
-                Color::GREEN,
-            );
-            lines.line_colored(
-                Vec3::new(border_pos.x + grid_size.x, border_pos.y, 1.),
-                Vec3::new(border_pos.x + grid_size.x, border_pos.y + grid_size.y, 1.),
-                0.9,
-                Color::GREEN,
-            );
-            lines.line_colored(
-                Vec3::new(border_pos.x, border_pos.y + grid_size.y, 1.),
-                Vec3::new(border_pos.x + grid_size.x, border_pos.y + grid_size.y, 1.),
-                0.9,
-                Color::GREEN,
-            );
-
-            println!(
-                "World coords: {}/{} [tile: {:?}]",
-                world_position.x, world_position.y, p
-            );
-            tile_info_event.send(TileInfoEvent { tile_pos: p });
-        } else {
-            println!("Cursor is not in the game window.");
+    // assuming there is exactly one main camera entity and one window
+    let tile = match (camera_q.get_single(), q_windows.get_single()) {
+        (Ok((camera, camera_transform)), Ok(window)) => {
+            cursor_tile_pos(window, camera, camera_transform, &map)
         }
+        _ => None,
+    };
+
+    if let Some(p) = tile {
+        draw_tile_outline(&mut lines, &map, &p, Color::GREEN);
+    }
+    if hovered.tile != tile {
+        hovered.tile = tile;
     }
 }
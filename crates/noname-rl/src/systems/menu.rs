@@ -0,0 +1,362 @@
+use bevy::{app::AppExit, prelude::*};
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    effects::prelude::MusicTrack,
+    events::DeathEvent,
+    resources::{Dungeon, GameRng, RLTimeSystem},
+    CameraMode, DebugFlags, DungeonLayout, FovDebug, GameEntity, GameLog, GameMap, GameState,
+    Health, HoveredTile, MapChunks, MenuButton, MenuScreen, Minimap, Monster, MyAssets,
+    PendingLoad, Player, RLAction, SaveGame, SaveGameCommand, SeedInputUI, Targeting, Travel,
+    UndoHistory,
+};
+
+const MAX_SEED_DIGITS: usize = 19;
+
+/// Seed typed on the main menu; a random one is used when it is empty.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SeedInput {
+    pub text: String,
+}
+
+/// Turn state to go back to when the pause menu is closed.
+#[derive(Resource, Debug, Clone)]
+pub struct ResumeState(pub GameState);
+
+fn menu_root(commands: &mut Commands, background: Color) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(background),
+                z_index: ZIndex::Global(20),
+                ..Default::default()
+            },
+            MenuScreen::default(),
+        ))
+        .id()
+}
+
+fn menu_text(parent: &mut ChildBuilder, assets: &MyAssets, text: impl Into<String>, size: f32) {
+    parent.spawn(
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font: assets.ui_font.clone(),
+                font_size: size,
+                color: Color::hex("fcfcfc").unwrap(),
+            },
+        )
+        .with_style(Style {
+            margin: UiRect::all(Val::Px(8.)),
+            ..Default::default()
+        }),
+    );
+}
+
+fn menu_button(parent: &mut ChildBuilder, assets: &MyAssets, label: &str, button: MenuButton) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(260.),
+                    padding: UiRect::all(Val::Px(6.)),
+                    margin: UiRect::all(Val::Px(4.)),
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::hex("193c3eff").unwrap()),
+                ..Default::default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: assets.ui_font.clone(),
+                    font_size: 24.0,
+                    color: Color::hex("f7d8bc").unwrap(),
+                },
+            ));
+        });
+}
+
+pub fn main_menu_setup(mut commands: Commands, assets: Res<MyAssets>, seed: Res<SeedInput>) {
+    // the game cameras are gone with the game
+    commands.spawn((Camera2dBundle::default(), MenuScreen::default()));
+
+    let root = menu_root(&mut commands, Color::hex("1d1816ff").unwrap());
+    commands.entity(root).with_children(|parent| {
+        menu_text(parent, &assets, "Noname RL", 56.);
+        menu_text(parent, &assets, "seed (empty for a random one):", 20.);
+        parent.spawn((
+            TextBundle::from_section(
+                seed.text.clone(),
+                TextStyle {
+                    font: assets.ui_font.clone(),
+                    font_size: 24.0,
+                    color: Color::hex("f7d8bc").unwrap(),
+                },
+            )
+            .with_style(Style {
+                min_width: Val::Px(260.),
+                min_height: Val::Px(28.),
+                margin: UiRect::bottom(Val::Px(12.)),
+                ..Default::default()
+            })
+            .with_background_color(Color::hex("3a2a2aff").unwrap()),
+            SeedInputUI::default(),
+        ));
+        menu_button(parent, &assets, "new game", MenuButton::NewGame);
+        if SaveGame::exists() {
+            menu_button(parent, &assets, "continue", MenuButton::Continue);
+        }
+        menu_button(parent, &assets, "quit", MenuButton::Quit);
+    });
+}
+
+pub fn pause_menu_setup(mut commands: Commands, assets: Res<MyAssets>) {
+    let root = menu_root(&mut commands, Color::hex("1d1816aa").unwrap());
+    commands.entity(root).with_children(|parent| {
+        menu_text(parent, &assets, "paused", 48.);
+        menu_button(parent, &assets, "resume", MenuButton::Resume);
+        menu_button(parent, &assets, "save and quit", MenuButton::SaveAndQuit);
+    });
+}
+
+pub fn game_over_setup(mut commands: Commands, assets: Res<MyAssets>, time: Res<RLTimeSystem>) {
+    let root = menu_root(&mut commands, Color::hex("3a1010cc").unwrap());
+    commands.entity(root).with_children(|parent| {
+        menu_text(parent, &assets, "you died", 56.);
+        menu_text(
+            parent,
+            &assets,
+            format!("after {} turns", time.get_time()),
+            24.,
+        );
+        menu_button(parent, &assets, "new game", MenuButton::NewGame);
+        menu_button(parent, &assets, "main menu", MenuButton::MainMenu);
+    });
+}
+
+pub fn victory_setup(mut commands: Commands, assets: Res<MyAssets>, time: Res<RLTimeSystem>) {
+    let root = menu_root(&mut commands, Color::hex("193c3ecc").unwrap());
+    commands.entity(root).with_children(|parent| {
+        menu_text(parent, &assets, "victory!", 56.);
+        menu_text(
+            parent,
+            &assets,
+            format!("the dungeon was cleared in {} turns", time.get_time()),
+            24.,
+        );
+        menu_button(parent, &assets, "new game", MenuButton::NewGame);
+        menu_button(parent, &assets, "main menu", MenuButton::MainMenu);
+    });
+}
+
+pub fn despawn_menu_screen(menu_q: Query<Entity, With<MenuScreen>>, mut commands: Commands) {
+    for entity in menu_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn menu_button_interaction(
+    mut buttons_q: Query<
+        (&Interaction, &MenuButton, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    state: Res<State<GameState>>,
+    resume: Option<Res<ResumeState>>,
+    mut seed: ResMut<SeedInput>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+    mut commands: Commands,
+) {
+    let hover_color = Color::hex("265c42ff").unwrap();
+    let normal_bg_color = Color::hex("193c3eff").unwrap();
+
+    for (interaction, button, mut bg_color) in buttons_q.iter_mut() {
+        match interaction {
+            Interaction::Hovered => bg_color.0 = hover_color,
+            Interaction::None => bg_color.0 = normal_bg_color,
+            Interaction::Pressed => {
+                info!("menu: {:?} in {:?}", button, state.get());
+                match button {
+                    MenuButton::NewGame => {
+                        // the seed is only typed on the main menu
+                        let typed = match state.get() {
+                            GameState::MainMenu => seed.text.parse::<u64>().ok(),
+                            _ => None,
+                        };
+                        let rng = typed.map(GameRng::new).unwrap_or_default();
+                        info!("new game with seed {}", rng.seed);
+                        seed.text.clear();
+                        commands.insert_resource(rng);
+                        next_state.set(GameState::AssetsLoaded);
+                    }
+                    MenuButton::Continue => match SaveGame::load() {
+                        Some(save) => {
+                            commands.insert_resource(GameRng::new(save.seed));
+                            commands.insert_resource(PendingLoad(save));
+                            next_state.set(GameState::AssetsLoaded);
+                        }
+                        None => warn!("no game to continue"),
+                    },
+                    MenuButton::Resume => {
+                        let resumed = resume
+                            .as_ref()
+                            .map(|resume| resume.0.clone())
+                            .unwrap_or(GameState::PlayerTurn);
+                        next_state.set(resumed);
+                    }
+                    MenuButton::SaveAndQuit => {
+                        commands.add(SaveGameCommand);
+                        next_state.set(GameState::MainMenu);
+                    }
+                    MenuButton::MainMenu => next_state.set(GameState::MainMenu),
+                    MenuButton::Quit => exit.send(AppExit),
+                }
+            }
+        }
+    }
+}
+
+/// Types the seed of the main menu; Enter starts the game.
+pub fn seed_input(
+    mut chars: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut seed: ResMut<SeedInput>,
+    mut seed_q: Query<&mut Text, With<SeedInputUI>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    for ev in chars.iter() {
+        if ev.char.is_ascii_digit() && seed.text.len() < MAX_SEED_DIGITS {
+            seed.text.push(ev.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        seed.text.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let rng = seed
+            .text
+            .parse::<u64>()
+            .map(GameRng::new)
+            .unwrap_or_default();
+        info!("new game with seed {}", rng.seed);
+        seed.text.clear();
+        commands.insert_resource(rng);
+        next_state.set(GameState::AssetsLoaded);
+    }
+    if seed.is_changed() {
+        for mut text in seed_q.iter_mut() {
+            text.sections[0].value = seed.text.clone();
+        }
+    }
+}
+
+/// Opens and closes the pause menu.
+pub fn toggle_pause(
+    action_q: Query<&ActionState<RLAction>, With<Player>>,
+    state: Res<State<GameState>>,
+    resume: Option<Res<ResumeState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    let pressed = action_q
+        .iter()
+        .any(|action| action.just_pressed(RLAction::Pause));
+    // waits for the pending transition (end of turn, game over)
+    if !pressed || next_state.0.is_some() {
+        return;
+    }
+    match state.get() {
+        GameState::PlayerTurn | GameState::EnemyTurn => {
+            commands.insert_resource(ResumeState(state.get().clone()));
+            next_state.set(GameState::Paused);
+        }
+        GameState::Paused => {
+            let resumed = resume
+                .as_ref()
+                .map(|resume| resume.0.clone())
+                .unwrap_or(GameState::PlayerTurn);
+            next_state.set(resumed);
+        }
+        _ => {}
+    }
+}
+
+/// Ends the game when the player dies, or wins it once every monster is
+/// dead. The save of a finished game is removed.
+pub fn detect_game_end(
+    mut death_events: EventReader<DeathEvent>,
+    player_q: Query<Entity, With<Player>>,
+    monsters_q: Query<&Health, With<Monster>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if death_events.is_empty() {
+        return;
+    }
+    let player_died = death_events
+        .iter()
+        .any(|ev| player_q.get(ev.entity).is_ok());
+    if !matches!(state.get(), GameState::PlayerTurn | GameState::EnemyTurn) {
+        return;
+    }
+
+    if player_died {
+        info!("game over");
+        SaveGame::delete();
+        next_state.set(GameState::GameOver);
+    } else if monsters_q.iter().all(|health| health.current <= 0) {
+        // the monsters killed this turn are only despawned later
+        info!("victory");
+        SaveGame::delete();
+        next_state.set(GameState::Victory);
+    }
+}
+
+/// Despawns everything belonging to the running game and resets the
+/// resources it used, so a new one starts from scratch.
+pub fn teardown_game(world: &mut World) {
+    let entities = world
+        .query_filtered::<Entity, Or<(With<GameEntity>, With<MusicTrack>)>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in entities {
+        // children are despawned with their parent
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+
+    world.remove_resource::<GameMap>();
+    world.remove_resource::<Minimap>();
+    world.insert_resource(MapChunks::default());
+    world.insert_resource(Targeting::default());
+    world.insert_resource(GameLog::default());
+    world.insert_resource(HoveredTile::default());
+    world.insert_resource(Dungeon::default());
+    world.insert_resource(Travel::default());
+    world.insert_resource(CameraMode::default());
+    world.insert_resource(DebugFlags::default());
+    world.insert_resource(DungeonLayout::default());
+    world.insert_resource(FovDebug::default());
+    if world.contains_resource::<UndoHistory>() {
+        world.insert_resource(UndoHistory::default());
+    }
+    // removed first so the systems keeping track of the clock see a new one
+    world.remove_resource::<RLTimeSystem>();
+    world.insert_resource(RLTimeSystem::new());
+}
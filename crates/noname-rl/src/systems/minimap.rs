@@ -0,0 +1,235 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    window::PrimaryWindow,
+};
+use bevy_ecs_tilemap::tiles::TilePos;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    GameEntity, GameMap, LogCategory, LogMessage, MinimapMarker, MinimapUI, Monster, Player,
+    RLAction, TileKind,
+};
+
+use super::travel::Travel;
+
+/// side of the minimap on screen, in pixels
+const MINIMAP_SIZE: f32 = 240.;
+const MARKER_SIZE: f32 = 4.;
+
+/// Minimap
+///
+/// Texture with one pixel per tile the player remembers, redrawn chunk by
+/// chunk as the map changes (see `update_minimap`).
+#[derive(Resource)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+}
+
+fn minimap_color(map: &GameMap, pos: &TilePos) -> [u8; 4] {
+    if !map.is_visited(pos) {
+        return [0, 0, 0, 0];
+    }
+    let [r, g, b] = match map.kind(pos) {
+        TileKind::Floor | TileKind::Rubble => [90, 90, 90],
+        TileKind::Wall | TileKind::GlassWall => [170, 170, 170],
+        TileKind::Door(_) => [170, 110, 50],
+        TileKind::Grass => [50, 130, 50],
+        TileKind::Water | TileKind::DeepWater => [40, 90, 200],
+        TileKind::Lava => [220, 80, 20],
+        TileKind::Chasm => [20, 20, 20],
+    };
+    // remembered tiles are dimmer than the ones in sight
+    let alpha = if map.is_visible(pos) { 255 } else { 150 };
+    [r, g, b, alpha]
+}
+
+pub fn minimap_setup(map: Res<GameMap>, mut images: ResMut<Assets<Image>>, mut commands: Commands) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: map.size.x,
+            height: map.size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    commands.spawn((
+        ImageBundle {
+            image: UiImage::new(image.clone()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.),
+                top: Val::Px(10.),
+                width: Val::Px(MINIMAP_SIZE),
+                height: Val::Px(MINIMAP_SIZE),
+                ..Default::default()
+            },
+            background_color: BackgroundColor(Color::WHITE),
+            z_index: ZIndex::Global(4),
+            ..Default::default()
+        },
+        Interaction::default(),
+        MinimapUI::default(),
+        GameEntity,
+    ));
+    commands.insert_resource(Minimap { image });
+}
+
+/// Redraws the chunks of the minimap texture changed since the last frame.
+pub fn update_minimap(
+    mut map: ResMut<GameMap>,
+    minimap: Res<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // taking the chunks must not look like a map change to `sync_map_chunks`
+    let chunks = map.bypass_change_detection().take_minimap_chunks();
+    if chunks.is_empty() {
+        return;
+    }
+    let image = match images.get_mut(&minimap.image) {
+        Some(image) => image,
+        None => return,
+    };
+
+    for chunk in chunks {
+        let (origin, size) = map.chunk_extent(chunk);
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                // image rows go downwards, map rows upwards
+                let index = (((map.size.y - 1 - y) * map.size.x + x) * 4) as usize;
+                image.data[index..index + 4]
+                    .copy_from_slice(&minimap_color(&map, &TilePos::new(x, y)));
+            }
+        }
+    }
+}
+
+/// Keeps the player and monster markers on top of the minimap, respawning
+/// them only when they move.
+pub fn update_minimap_markers(
+    map: Res<GameMap>,
+    player_q: Query<&TilePos, With<Player>>,
+    monsters_q: Query<&TilePos, With<Monster>>,
+    minimap_q: Query<Entity, With<MinimapUI>>,
+    markers_q: Query<Entity, With<MinimapMarker>>,
+    mut shown: Local<Vec<(TilePos, Color)>>,
+    mut commands: Commands,
+) {
+    let minimap = match minimap_q.get_single() {
+        Ok(minimap) => minimap,
+        Err(_) => return,
+    };
+
+    let markers = monsters_q
+        .iter()
+        .filter(|pos| map.is_visible(pos))
+        .map(|pos| (*pos, Color::RED))
+        .chain(player_q.iter().map(|pos| (*pos, Color::YELLOW)))
+        .collect::<Vec<_>>();
+    // the markers are gone when a new game was started
+    if *shown == markers && !markers_q.is_empty() {
+        return;
+    }
+
+    for marker in markers_q.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+    let scale = MINIMAP_SIZE / map.size.x as f32;
+    commands.entity(minimap).with_children(|parent| {
+        for (pos, color) in markers.iter() {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(pos.x as f32 * scale - MARKER_SIZE / 2.),
+                        top: Val::Px((map.size.y - 1 - pos.y) as f32 * scale - MARKER_SIZE / 2.),
+                        width: Val::Px(MARKER_SIZE),
+                        height: Val::Px(MARKER_SIZE),
+                        ..Default::default()
+                    },
+                    background_color: BackgroundColor(*color),
+                    ..Default::default()
+                },
+                MinimapMarker::default(),
+            ));
+        }
+    });
+    *shown = markers;
+}
+
+pub fn toggle_minimap(
+    action_q: Query<&ActionState<RLAction>, With<Player>>,
+    mut minimap_q: Query<&mut Visibility, With<MinimapUI>>,
+) {
+    let pressed = action_q
+        .iter()
+        .any(|action| action.just_pressed(RLAction::ToggleMinimap));
+    if !pressed {
+        return;
+    }
+    for mut visibility in minimap_q.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Clicking a remembered tile on the minimap makes the player travel there.
+pub fn minimap_click(
+    minimap_q: Query<
+        (&Interaction, &Node, &GlobalTransform),
+        (Changed<Interaction>, With<MinimapUI>),
+    >,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<&TilePos, With<Player>>,
+    monsters_q: Query<(Entity, &TilePos), With<Monster>>,
+    map: Res<GameMap>,
+    mut travel: ResMut<Travel>,
+    mut commands: Commands,
+) {
+    let (node, transform) = match minimap_q.get_single() {
+        Ok((Interaction::Pressed, node, transform)) => (node, transform),
+        _ => return,
+    };
+    let cursor = match q_windows
+        .get_single()
+        .ok()
+        .and_then(|w| w.cursor_position())
+    {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let player_pos = match player_q.get_single() {
+        Ok(pos) => *pos,
+        Err(_) => return,
+    };
+
+    let rect = Rect::from_center_size(transform.translation().truncate(), node.size());
+    let ratio = (cursor - rect.min) / rect.size();
+    let x = (ratio.x * map.size.x as f32) as i32;
+    let y = map.size.y as i32 - 1 - (ratio.y * map.size.y as f32) as i32;
+    let goal = match map.checked_pos(IVec2::new(x, y)) {
+        Some(goal) if map.is_visited(&goal) => goal,
+        _ => return,
+    };
+
+    match map.find_known_path(player_pos, goal) {
+        Some(path) => {
+            info!("travel to {:?}", goal);
+            let seen = monsters_q
+                .iter()
+                .filter(|(_, pos)| map.is_visible(pos))
+                .map(|(monster, _)| monster)
+                .collect();
+            travel.start(path, seen);
+        }
+        None => commands.add(LogMessage::new(
+            LogCategory::Blocked,
+            "you don't know the way there",
+        )),
+    }
+}
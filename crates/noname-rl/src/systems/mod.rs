@@ -1,9 +1,23 @@
 use bevy::prelude::Component;
 
+mod camera;
+mod combat;
+mod console;
+mod debug_overlay;
+mod game_log;
+mod hud;
 mod input;
+mod items;
 mod map_tile_info;
+mod menu;
+mod minimap;
 mod monsters;
+mod options;
+mod progression;
 mod setup;
+mod status;
+mod targeting;
+mod travel;
 mod ui;
 mod update;
 // prelude
@@ -12,10 +26,24 @@ mod update;
 pub struct GameUiCamera;
 
 pub mod prelude {
+    pub use super::camera::*;
+    pub use super::combat::*;
+    pub use super::console::*;
+    pub use super::debug_overlay::*;
+    pub use super::game_log::*;
+    pub use super::hud::*;
     pub use super::input::*;
+    pub use super::items::*;
     pub use super::map_tile_info::*;
+    pub use super::menu::*;
+    pub use super::minimap::*;
     pub use super::monsters::*;
+    pub use super::options::*;
+    pub use super::progression::*;
     pub use super::setup::*;
+    pub use super::status::*;
+    pub use super::targeting::*;
+    pub use super::travel::*;
     pub use super::ui::*;
     pub use super::update::*;
     pub use super::GameUiCamera;
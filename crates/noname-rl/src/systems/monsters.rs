@@ -1,8 +1,11 @@
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
-use rand::Rng;
+use rand::{distributions::WeightedIndex, prelude::*};
 
-use crate::{algorithms::tile_pos_to_world_pos, MyAssets, StatsBundle, TileMapLayer0, Wall};
+use crate::{
+    resources::GameRng, CanOpenDoors, GameEntity, GameMap, MonsterDefinition, MonsterKind,
+    MonsterTable, MyAssets, StatsBundle, StatusEffects, TileKind,
+};
 
 #[derive(Component, Default)]
 pub struct Monster;
@@ -10,6 +13,8 @@ pub struct Monster;
 #[derive(Bundle, Default)]
 pub struct MonsterBundle {
     pub monster: Monster,
+    pub can_open_doors: CanOpenDoors,
+    pub status_effects: StatusEffects,
     // pub visible_tiles: VisibleTiles,
     // pub visited_tiles: VisitedTiles,
     // pub tile_pos: TilePos,
@@ -17,43 +22,65 @@ pub struct MonsterBundle {
     // pub global_transform: GlobalTransform,
     // pub animator: Animator<RLAction>,
     // pub needs_fov_update: NeedsFovUpdate,
-    // pub walking_audio_effect: WalkingAudioEffect,
 }
 
 pub fn spawn_monster(
     mut commands: Commands,
     assets: Res<MyAssets>,
-    map_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType), With<TileMapLayer0>>,
-    floor_tiles_q: Query<(&TilePos), (With<TilemapId>, Without<Wall>)>,
+    map: Res<GameMap>,
+    table: Res<MonsterTable>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    let floor_tiles: Vec<TilePos> = floor_tiles_q.iter().copied().collect();
+    let rng = &mut game_rng.rng;
+    let floor_tiles: Vec<TilePos> = map.tiles_of_kind(TileKind::Floor);
+
+    let definitions = table
+        .iter()
+        .filter(|monster| monster.spawn_weight > 0)
+        .collect::<Vec<_>>();
+    let weights = WeightedIndex::new(definitions.iter().map(|monster| monster.spawn_weight))
+        .expect("no monster can be spawned");
 
     let n_monsters = 100;
 
     for _ in 0..n_monsters {
-        let (map_size, grid_size, map_type) = map_q.single();
-
-        let mut rng = rand::thread_rng();
-
+        let definition = definitions[weights.sample(rng)];
         let tile_pos = floor_tiles[rng.gen_range(0..floor_tiles.len())];
 
-        let pos = tile_pos_to_world_pos(&tile_pos, map_size, grid_size, map_type).unwrap();
+        spawn_monster_at(&mut commands, &assets, &map, definition, tile_pos);
+    }
+}
+
+/// Spawns a monster of the given kind, with the stats of its definition.
+pub fn spawn_monster_at(
+    commands: &mut Commands,
+    assets: &MyAssets,
+    map: &GameMap,
+    definition: &MonsterDefinition,
+    tile_pos: TilePos,
+) -> Entity {
+    let pos = map.tile_to_world(&tile_pos);
 
-        commands.spawn((
+    commands
+        .spawn((
             SpriteSheetBundle {
                 texture_atlas: assets.sprites.clone(),
                 sprite: TextureAtlasSprite {
-                    index: 25,
+                    index: definition.sprite,
                     custom_size: Some(Vec2::new(16., 16.)),
                     ..Default::default()
                 },
-                transform: Transform::from_xyz(pos.x as f32, pos.y as f32, 6.0),
+                transform: Transform::from_xyz(pos.x, pos.y, 6.0),
                 ..Default::default()
             },
             MonsterBundle::default(),
-            TilePos::new(tile_pos.x, tile_pos.y),
-            Name::new("Monster"),
-            StatsBundle::default(),
-        ));
-    }
+            tile_pos,
+            Name::new(definition.name.clone()),
+            MonsterKind {
+                id: definition.id.clone(),
+            },
+            StatsBundle::new(definition.health, definition.attack, definition.defense),
+            GameEntity,
+        ))
+        .id()
 }
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    AudioSettings, GameEntity, MyAssets, OptionsUI, Player, RLAction, VolumeButton, VolumeChannel,
+    VolumeLabel, VOLUME_STEP,
+};
+
+fn volume_text(settings: &AudioSettings, channel: VolumeChannel) -> String {
+    format!("{}: {:.0}%", channel.label(), settings.get(channel) * 100.)
+}
+
+pub fn options_ui_setup(
+    mut commands: Commands,
+    assets: Res<MyAssets>,
+    settings: Res<AudioSettings>,
+) {
+    let bg_color: Color = Color::hex("1d1816ee").unwrap();
+    let text_color = Color::hex("fcfcfc").unwrap();
+    let data_color = Color::hex("f7d8bc").unwrap();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    left: Val::Percent(25.),
+                    top: Val::Percent(25.),
+                    width: Val::Percent(40.),
+                    padding: UiRect::all(Val::Px(10.)),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(bg_color),
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            OptionsUI::default(),
+            GameEntity,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "options",
+                TextStyle {
+                    font: assets.ui_font.clone(),
+                    font_size: 28.0,
+                    color: text_color,
+                },
+            ));
+            for channel in VolumeChannel::ALL {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            margin: UiRect {
+                                top: Val::Px(10.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .with_children(|row| {
+                        for (label, delta) in [("-", -VOLUME_STEP), ("+", VOLUME_STEP)] {
+                            if delta > 0. {
+                                row.spawn((
+                                    TextBundle::from_section(
+                                        volume_text(&settings, channel),
+                                        TextStyle {
+                                            font: assets.ui_font.clone(),
+                                            font_size: 22.0,
+                                            color: data_color,
+                                        },
+                                    )
+                                    .with_style(Style {
+                                        width: Val::Px(220.),
+                                        margin: UiRect::horizontal(Val::Px(10.)),
+                                        ..Default::default()
+                                    }),
+                                    VolumeLabel { channel },
+                                ));
+                            }
+                            row.spawn((
+                                ButtonBundle {
+                                    background_color: BackgroundColor(
+                                        Color::hex("193c3eff").unwrap_or(bg_color),
+                                    ),
+                                    style: Style {
+                                        padding: UiRect::horizontal(Val::Px(8.)),
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                },
+                                VolumeButton { channel, delta },
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    label,
+                                    TextStyle {
+                                        font: assets.ui_font.clone(),
+                                        font_size: 22.0,
+                                        color: text_color,
+                                    },
+                                ));
+                            });
+                        }
+                    });
+            }
+        });
+}
+
+/// Opens and closes the options menu.
+pub fn options_ui_toggle(
+    action_q: Query<&ActionState<RLAction>, With<Player>>,
+    mut options_ui: Query<&mut Visibility, With<OptionsUI>>,
+) {
+    let pressed = action_q
+        .iter()
+        .any(|action| action.just_pressed(RLAction::Options));
+    if !pressed {
+        return;
+    }
+    if let Ok(mut visibility) = options_ui.get_single_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+pub fn options_ui_interaction(
+    buttons_q: Query<(&Interaction, &VolumeButton), Changed<Interaction>>,
+    mut settings: ResMut<AudioSettings>,
+) {
+    for (interaction, button) in buttons_q.iter() {
+        if *interaction == Interaction::Pressed {
+            settings.adjust(button.channel, button.delta);
+        }
+    }
+}
+
+pub fn options_ui_update(
+    settings: Res<AudioSettings>,
+    mut labels_q: Query<(&mut Text, &VolumeLabel)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (mut text, label) in labels_q.iter_mut() {
+        text.sections[0].value = volume_text(&settings, label.channel);
+    }
+}
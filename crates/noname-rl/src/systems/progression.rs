@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+
+use crate::{
+    events::{DeathEvent, LevelUpEvent},
+    resources::RLTimeSystem,
+    Attack, Defense, Experience, GameEntity, Health, LevelUpChoice, LevelUpUI, MonsterKind,
+    MonsterTable, MyAssets, Player, ReplayRecorder, ReplayStep, LEVEL_UP_ATTACK_EVERY,
+    LEVEL_UP_HEALTH,
+};
+
+/// Rewards the killers of monsters with the xp of the monster kind.
+pub fn grant_experience(
+    mut death_events: EventReader<DeathEvent>,
+    kinds_q: Query<&MonsterKind>,
+    mut killers_q: Query<(&mut Experience, &mut Health, &mut Attack)>,
+    table: Res<MonsterTable>,
+    mut level_up_events: EventWriter<LevelUpEvent>,
+) {
+    for event in death_events.iter() {
+        let killer = match event.killer {
+            Some(killer) => killer,
+            None => continue,
+        };
+        let xp = match kinds_q
+            .get(event.entity)
+            .ok()
+            .and_then(|k| table.get(&k.id))
+        {
+            Some(definition) => definition.xp,
+            None => continue,
+        };
+        let (mut experience, mut health, mut attack) = match killers_q.get_mut(killer) {
+            Ok(killer) => killer,
+            Err(_) => continue,
+        };
+
+        info!("{:?} gains {} xp", killer, xp);
+        let first_level = experience.level + 1;
+        experience.gain(xp);
+        for level in first_level..=experience.level {
+            health.max += LEVEL_UP_HEALTH;
+            health.current += LEVEL_UP_HEALTH;
+            if level % LEVEL_UP_ATTACK_EVERY == 0 {
+                attack.damage += 1;
+            }
+            info!("{:?} reaches level {}", killer, level);
+            level_up_events.send(LevelUpEvent {
+                entity: killer,
+                level,
+            });
+        }
+    }
+}
+
+pub fn level_up_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
+    let bg_color: Color = Color::hex("1d1816ee").unwrap();
+    let text_color = Color::hex("fcfcfc").unwrap();
+    let data_color = Color::hex("f7d8bc").unwrap();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    left: Val::Percent(25.),
+                    top: Val::Percent(30.),
+                    width: Val::Percent(40.),
+                    padding: UiRect::all(Val::Px(10.)),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(bg_color),
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            LevelUpUI::default(),
+            GameEntity,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "level up! choose a stat",
+                TextStyle {
+                    font: assets.ui_font.clone(),
+                    font_size: 28.0,
+                    color: text_color,
+                },
+            ));
+            for (index, choice) in LevelUpChoice::ALL.iter().enumerate() {
+                builder
+                    .spawn((
+                        ButtonBundle {
+                            background_color: BackgroundColor(
+                                Color::hex("193c3eff").unwrap_or(bg_color),
+                            ),
+                            style: Style {
+                                margin: UiRect {
+                                    top: Val::Px(10.),
+                                    ..Default::default()
+                                },
+                                padding: UiRect::all(Val::Px(4.)),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        *choice,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            format!("{}: {}", index + 1, choice.label()),
+                            TextStyle {
+                                font: assets.ui_font.clone(),
+                                font_size: 22.0,
+                                color: data_color,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+/// Shows the level-up screen while the player has levels to spend.
+pub fn level_up_ui_update(
+    player_q: Query<&Experience, (With<Player>, Changed<Experience>)>,
+    mut level_up_ui: Query<&mut Visibility, With<LevelUpUI>>,
+) {
+    let experience = match player_q.get_single() {
+        Ok(experience) => experience,
+        Err(_) => return,
+    };
+    if let Ok(mut visibility) = level_up_ui.get_single_mut() {
+        *visibility = if experience.pending_level_ups > 0 {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+type LevelUpQueryData = (
+    &'static mut Experience,
+    &'static mut Health,
+    &'static mut Attack,
+    &'static mut Defense,
+);
+
+/// Spends a pending level-up on the stat picked with the buttons or the
+/// number keys.
+pub fn level_up_choice(
+    mut player_q: Query<LevelUpQueryData, With<Player>>,
+    buttons_q: Query<(&Interaction, &LevelUpChoice), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    time_system: Res<RLTimeSystem>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    let (mut experience, mut health, mut attack, mut defense) = match player_q.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    if experience.pending_level_ups == 0 {
+        return;
+    }
+
+    let pressed = buttons_q
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, choice)| *choice);
+    let choice = pressed.or_else(|| {
+        [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3]
+            .iter()
+            .zip(LevelUpChoice::ALL)
+            .find(|(key, _)| keys.just_pressed(**key))
+            .map(|(_, choice)| choice)
+    });
+    let choice = match choice {
+        Some(choice) => choice,
+        None => return,
+    };
+
+    info!("level-up choice: {:?}", choice);
+    choice.apply(&mut health, &mut attack, &mut defense);
+    experience.pending_level_ups -= 1;
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.record(time_system.get_time(), ReplayStep::LevelUp { choice });
+    }
+}
@@ -1,14 +1,15 @@
 #![allow(dead_code, unused_variables)]
 use crate::{
-    algorithms::tile_pos_to_world_pos,
     bresenham_line,
-    resources::RLRandomGenerator,
+    resources::{GameRng, RLRandomGenerator},
     room::{self, Room},
-    FovOccluder, GameState, MyAssets, NeedsFovUpdate, Player, PlayerBundle, RLAction, StatsBundle,
-    TileKind, TileMapEntityLayer, TileMapLayer0, TileMapVisibilityLayer, VisitedTiles,
-    WalkingAudioEffect, Wall, WallBundle,
+    DoorState, GameEntity, GameMap, GameState, MyAssets, NeedsFovUpdate, Player, PlayerBundle,
+    RLAction, StatsBundle, TileKind, VisitedTiles,
+};
+use bevy::{
+    prelude::*, render::camera::Viewport, transform::commands, ui::camera_config, utils::HashSet,
+    window::PrimaryWindow,
 };
-use bevy::{prelude::*, render::camera::Viewport, transform::commands, ui::camera_config};
 use bevy_ecs_tilemap::prelude::*;
 use bevy_inspector_egui::egui::{plot::Line, Stroke};
 use bevy_prototype_debug_lines::*;
@@ -21,6 +22,8 @@ use noise::*;
 use rand::{seq::SliceRandom, Rng};
 use shape::*;
 
+use super::camera::{game_viewport, CameraController, FOLLOW_SCALE};
+
 #[derive(Component, Default)]
 pub struct MyGameCamera;
 
@@ -40,6 +43,21 @@ pub fn setup_player(assets: Res<MyAssets>, mut commands: Commands) {
     input_map.insert(KeyCode::Right, RLAction::Right);
     input_map.insert(GamepadButtonType::DPadRight, RLAction::Right);
 
+    input_map.insert(KeyCode::O, RLAction::OpenDoor);
+    input_map.insert(KeyCode::C, RLAction::CloseDoor);
+
+    input_map.insert(KeyCode::G, RLAction::PickUp);
+    input_map.insert(KeyCode::D, RLAction::Drop);
+    input_map.insert(KeyCode::Tab, RLAction::NextItem);
+    input_map.insert(KeyCode::U, RLAction::UseItem);
+    input_map.insert(KeyCode::E, RLAction::Equip);
+    input_map.insert(KeyCode::R, RLAction::Unequip);
+    input_map.insert(KeyCode::F, RLAction::Fire);
+    input_map.insert(KeyCode::Tab, RLAction::NextTarget);
+
+    input_map.insert(KeyCode::Return, RLAction::Confirm);
+    input_map.insert(KeyCode::Back, RLAction::Cancel);
+
     commands.spawn((
         SpriteSheetBundle {
             texture_atlas: assets.sprites.clone(),
@@ -60,12 +78,14 @@ pub fn setup_player(assets: Res<MyAssets>, mut commands: Commands) {
             ..Default::default()
         },
         NeedsFovUpdate,
-        StatsBundle::default(),
+        StatsBundle::new(20, 3, 0),
+        GameEntity,
     ));
 }
 
 pub fn setup_camera(
     mut commands: Commands,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
     // mut q: Query<&mut OrthographicProjection, With<MyGameCamera>>,
 ) {
     println!("setup_camera");
@@ -73,19 +93,14 @@ pub fn setup_camera(
     commands.spawn((
         Camera2dBundle {
             camera: Camera {
-                // set the viewport to a 256x256 square in the top left corner
-                viewport: Some(Viewport {
-                    physical_position: UVec2::new(0, 0),
-                    physical_size: UVec2::new(1100, 900),
-
-                    ..default()
-                }),
+                // kept in sync with the window size by `update_camera_viewport`
+                viewport: q_windows.get_single().ok().map(game_viewport),
 
                 ..default()
             },
             projection: OrthographicProjection {
                 far: 1000.,
-                scale: 0.5, // double the size of everything
+                scale: FOLLOW_SCALE, // double the size of everything
                 near: -1000.,
 
                 ..Default::default()
@@ -93,7 +108,9 @@ pub fn setup_camera(
             ..default()
         },
         MyGameCamera,
+        CameraController::default(),
         UiCameraConfig { show_ui: false },
+        GameEntity,
     ));
     // commands.spawn((Camera2dBundle::default(), GameUiCamera));
 }
@@ -124,18 +141,10 @@ pub fn fill_positions(
 use noise::Perlin;
 
 pub fn map_noise(
-    mut map_q: Query<
-        (
-            &mut TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-        ),
-        With<TileMapLayer0>,
-    >,
-    mut commands: Commands, // rng: Res<RLRandomGenerator<Fbm<Perlin>>>,
+    mut map: ResMut<GameMap>,
+    // rng: Res<RLRandomGenerator<Fbm<Perlin>>>,
 ) {
-    let (mut tile_storage, map_size, grid_size, map_type) = map_q.single_mut();
+    let map_size = map.size;
 
     // let mut noise_f : Fbm<Perlin> = rng.noise.clone();
     // noise_f = noise_f.set_seed(1);
@@ -144,44 +153,70 @@ pub fn map_noise(
     // noise_f = noise_f.set_lacunarity(2.0);
     // noise_f = noise_f.set_persistence(0.5);
     let perlin_noise = Perlin::default();
+    let hazard_noise = Perlin::new(1);
 
+    // sprinkle terrain features on the open ground
     for x in 0..map_size.x {
         for y in 0..map_size.y {
             let tile_pos = TilePos { x, y };
-            let tile_entity = tile_storage.get(&tile_pos).unwrap();
-
-            let value = perlin_noise.get([
-                (x as f32 / map_size.x as f32) as f64,
-                (y as f32 / map_size.y as f32) as f64,
-            ]) as f32;
-
-            if value > 0. {
-                commands.entity(tile_entity).insert((TileTextureIndex(205)));
+            if map.kind(&tile_pos) != TileKind::Floor {
+                continue;
             }
+
+            let point = [
+                (x as f32 / map_size.x as f32) as f64 * 10.,
+                (y as f32 / map_size.y as f32) as f64 * 10.,
+            ];
+            let value = perlin_noise.get(point) as f32;
+            let hazard = hazard_noise.get(point) as f32;
+
+            let kind = if value > 0.45 {
+                TileKind::DeepWater
+            } else if value > 0.3 {
+                TileKind::Water
+            } else if value < -0.3 {
+                TileKind::Grass
+            } else if hazard > 0.5 {
+                TileKind::Lava
+            } else if hazard < -0.5 {
+                TileKind::Chasm
+            } else if hazard < -0.4 {
+                TileKind::Rubble
+            } else {
+                continue;
+            };
+            map.set_terrain(&tile_pos, kind);
         }
     }
 }
 
+/// DungeonLayout
+///
+/// Rooms and corridors laid out by `map_room_generator`, drawn by the
+/// debug overlays.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct DungeonLayout {
+    pub rooms: Vec<Room>,
+    /// centers of the connected rooms
+    pub corridors: Vec<(IVec2, IVec2)>,
+}
+
 pub fn map_room_generator(
-    mut map_q: Query<
-        (
-            &mut TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-        ),
-        With<TileMapLayer0>,
-    >,
+    mut map: ResMut<GameMap>,
     mut q: Query<(Entity, &mut Transform, &mut TilePos), With<Player>>,
-    mut commands: Commands, // rng: Res<RLRandomGenerator<Fbm<Perlin>>>,
+    mut game_rng: ResMut<GameRng>,
+    mut layout: ResMut<DungeonLayout>,
+    // rng: Res<RLRandomGenerator<Fbm<Perlin>>>,
 ) {
-    let (mut tile_storage, map_size, grid_size, map_type) = map_q.single_mut();
+    let rng = &mut game_rng.rng;
+    let map_size = map.size;
     let mut rooms = Vec::<Room>::new();
 
     let mut attempts = 0;
-    let map_extent = map_size;
+    let map_extent = &map_size;
     while rooms.len() < 4 && attempts < 1000 {
         let candidate = Room::create_random_in_rect(
+            rng,
             IVec2::new(0, 0),
             IVec2::new(map_extent.x as i32, map_extent.y as i32),
             (10..25, 10..25),
@@ -195,266 +230,117 @@ pub fn map_room_generator(
 
     for room in rooms.iter() {
         for cell in room.border_cells() {
-            let tile_pos = TilePos::new(cell.x as u32, cell.y as u32);
-            if let Some(tile_entity) = tile_storage.checked_get(&tile_pos) {
-                commands
-                    .entity(tile_entity)
-                    .insert((WallBundle::default(), TileTextureIndex(650)));
+            if let Some(tile_pos) = map.checked_pos(cell) {
+                if rng.gen::<f32>() < 0.05 {
+                    map.set_terrain(&tile_pos, TileKind::GlassWall);
+                } else {
+                    map.set(&tile_pos, TileKind::Wall, 650);
+                }
             }
         }
 
         for cell in room.interior_cells() {
-            let tile_pos = TilePos::new(cell.x as u32, cell.y as u32);
-            if let Some(tile_entity) = tile_storage.checked_get(&tile_pos) {
-                commands
-                    .entity(tile_entity)
-                    .insert((TileKind::Floor, TileTextureIndex(4)))
-                    .remove::<Wall>()
-                    .remove::<FovOccluder>();
+            if let Some(tile_pos) = map.checked_pos(cell) {
+                map.set(&tile_pos, TileKind::Floor, 4);
             }
         }
     }
 
+    let border_tiles = rooms
+        .iter()
+        .flat_map(|room| room.border_cells())
+        .filter_map(|cell| map.checked_pos(cell))
+        .collect::<HashSet<_>>();
+
     // connect rooms centers
     let mut corridor_tiles = Vec::<TilePos>::new();
+    let mut doorway_tiles = Vec::<TilePos>::new();
+    layout.corridors.clear();
     for i in 0..rooms.len() - 1 {
         let room_a = &rooms[i];
         let room_b = &rooms[i + 1];
 
         let start = room_a.center();
         let end = room_b.center();
+        layout.corridors.push((start, end));
 
         for i in -1..=1 {
             let positions = bresenham_line(
                 IVec2::new(start.x + i as i32, start.y as i32),
                 IVec2::new(end.x + i as i32, end.y as i32),
-                map_size,
+                &map_size,
             );
 
+            if i == 0 {
+                doorway_tiles.extend(positions.iter().filter(|pos| border_tiles.contains(pos)));
+            }
             corridor_tiles.extend(positions);
         }
     }
 
-    for tile in corridor_tiles.iter() {
-        let tile_pos = TilePos::new(tile.x as u32, tile.y as u32);
-        if let Some(tile_entity) = tile_storage.checked_get(&tile_pos) {
-            commands
-                .entity(tile_entity)
-                .insert((TileKind::Floor, TileTextureIndex(54)))
-                .remove::<Wall>()
-                .remove::<FovOccluder>();
+    for tile_pos in corridor_tiles.iter() {
+        if border_tiles.contains(tile_pos) {
+            // the doorway is only as wide as the door
+            map.set(tile_pos, TileKind::Wall, 650);
+        } else {
+            map.set(tile_pos, TileKind::Floor, 54);
+        }
+    }
+
+    // the keys of the locked doors are placed by `spawn_items`
+    for tile_pos in doorway_tiles.iter() {
+        if rng.gen::<f32>() < 0.25 {
+            map.set_door(tile_pos, DoorState::Locked { key: 0 });
+        } else {
+            map.set_door(tile_pos, DoorState::Closed);
         }
     }
 
     // get a random cell in a random room
-    let mut rng = rand::thread_rng();
-    let room = rooms.choose(&mut rng).unwrap();
+    let room = rooms.choose(rng).unwrap();
     let interior_cells = room.interior_cells();
-    let cell = interior_cells.choose(&mut rng).unwrap();
+    let cell = interior_cells.choose(rng).unwrap();
 
-    let pos = tile_pos_to_world_pos(
-        &TilePos::new(cell.x as u32, cell.y as u32),
-        map_size,
-        grid_size,
-        map_type,
-    )
-    .unwrap();
+    let pos = map.tile_to_world(&TilePos::new(cell.x as u32, cell.y as u32));
 
     let (e, mut player_transform, mut player_pos) = q.single_mut();
 
     player_pos.x = cell.x as u32;
     player_pos.y = cell.y as u32;
 
-    player_transform.translation.x = pos.x as f32;
-    player_transform.translation.y = pos.y as f32;
+    player_transform.translation.x = pos.x;
+    player_transform.translation.y = pos.y;
 
-    dbg!(rooms);
+    layout.rooms = rooms;
 }
 
 pub fn map_setup(
-    my_assets: Res<MyAssets>,
     // mut player_q: Query<(Entity, &mut Player), With<Player>>,
     mut commands: Commands,
-    mut camera_q: Query<(&mut Transform, &Camera), (With<MyGameCamera>, Without<Player>)>,
-    mut game_state: ResMut<State<GameState>>,
+    mut game_rng: ResMut<GameRng>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    // let (e, mut player) = player_q.get_single_mut().unwrap_or_else(|_| {
-    //     panic!("There must be exactly one player entity with a Player component in the game world.")
-    // });
-
-    let (mut _camera_transform, camera) = camera_q.single_mut();
-    let texture_handle: Handle<Image> = my_assets.player.clone();
-    let visibility_image_handle = my_assets.visibility_image.clone();
-
     let map_size = TilemapSize { x: 320, y: 320 };
+    let tile_size = TilemapTileSize { x: 16.0, y: 16.0 };
 
-    // let viewport_size = camera
-    //     .clone()
-    //     .viewport
-    //     .unwrap_or_default()
-    //     .physical_size
-    //     .clone();
+    // the tilemap entities are spawned lazily by `update_map_chunks`
+    let mut map = GameMap::new(map_size, tile_size);
+    let rng = &mut game_rng.rng;
 
-    let tile_size = TilemapTileSize { x: 16.0, y: 16.0 };
-    let grid_size = tile_size.into();
-    let map_type = TilemapType::default();
-
-    // Layer 1
-    let mut tile_storage = TileStorage::empty(map_size);
-    let tilemap_entity = commands.spawn_empty().id();
-
-    fill_tilemap(
-        TileTextureIndex(4),
-        map_size,
-        TilemapId(tilemap_entity),
-        &mut commands,
-        &mut tile_storage,
-    );
-    let mut rng = rand::thread_rng();
-
-    for c in tile_storage.iter_mut() {
-        if rng.gen::<f32>() > 0.7 {
-            commands
-                .entity(c.unwrap())
-                .insert((WallBundle::default(), TileTextureIndex(35)));
-        } else {
-            // commands.entity(c.unwrap()).insert(TileKind::Floor);
+    for x in 0..map_size.x {
+        for y in 0..map_size.y {
+            let tile_pos = TilePos { x, y };
+            if rng.gen::<f32>() > 0.7 {
+                map.set(&tile_pos, TileKind::Wall, 35);
+            } else {
+                map.set(&tile_pos, TileKind::Floor, 4);
+            }
         }
     }
 
-    commands.entity(tilemap_entity).insert((
-        TilemapBundle {
-            grid_size,
-            map_type,
-            size: map_size,
-            storage: tile_storage,
-            texture: TilemapTexture::Single(texture_handle.clone()),
-            tile_size,
-            // transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
-            ..Default::default()
-        },
-        TileMapLayer0,
-    ));
-
-    // // Layer 2
-    // let mut tile_storage = TileStorage::empty(map_size);
-    // let tilemap_entity = commands.spawn_empty().id();
-
-    // // fill_tilemap(
-    // //     TileTextureIndex(2),
-    // //     map_size,
-    // //     TilemapId(tilemap_entity),
-    // //     &mut commands,
-    // //     &mut tile_storage,
-    // // );
-    // let start = TilePos::new(0, 0);
-    // let end = TilePos::new(7, 3);
-    // // let tilemap_id = TilemapId(tilemap_entity);
-    // let positions = bresenham_line(
-    //     IVec2::new(start.x as i32, start.y as i32),
-    //     IVec2::new(end.x as i32, end.y as i32),
-    //     &map_size,
-    // );
-
-    // fill_positions(
-    //     TileTextureIndex(34),
-    //     positions,
-    //     TilemapId(tilemap_entity),
-    //     &mut commands,
-    //     &mut tile_storage,
-    // );
-
-    // commands.entity(tilemap_entity).insert((TilemapBundle {
-    //     grid_size,
-    //     map_type,
-    //     size: map_size,
-    //     storage: tile_storage,
-    //     texture: TilemapTexture::Single(texture_handle.clone()),
-    //     tile_size: TilemapTileSize { x: 16.0, y: 16.0 },
-    //     // transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 1.0)
-    //     //     * Transform::from_xyz(0.0, 0.0, 0.0),
-    //     ..Default::default()
-    // },));
-
-    // Visibiility Layer
-    let mut tile_storage = TileStorage::empty(map_size);
-    let tilemap_entity = commands.spawn_empty().id();
-
-    fill_tilemap(
-        TileTextureIndex(0),
-        map_size,
-        TilemapId(tilemap_entity),
-        &mut commands,
-        &mut tile_storage,
-    );
-
-    commands.entity(tilemap_entity).insert((
-        TilemapBundle {
-            grid_size,
-            map_type,
-            size: map_size,
-            storage: tile_storage,
-            texture: TilemapTexture::Single(visibility_image_handle),
-            tile_size: TilemapTileSize { x: 16.0, y: 16.0 },
-            // transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 2.0)
-            //     * Transform::from_xyz(0.0, 0.0, 0.0),
-            ..Default::default()
-        },
-        TileMapVisibilityLayer,
-    ));
+    commands.insert_resource(map);
 
-    // Visibiility Layer
-    // let mut entity_tile_storage = TileStorage::empty(map_size);
-    // let tilemap_entity = commands.spawn_empty().id();
-
-    // fill_tilemap(
-    //     TileTextureIndex(0),
-    //     map_size,
-    //     TilemapId(tilemap_entity),
-    //     &mut commands,
-    //     &mut tile_storage,
-    // );
-
-    // let tile_pos = TilePos::new(map_size.x / 2, map_size.y / 2);
-    // entity_tile_storage.set(&tile_pos, e);
-
-    // commands.entity(e).insert(TileBundle {
-    //     position: tile_pos,
-    //     tilemap_id: TilemapId(tilemap_entity),
-    //     texture_index: TileTextureIndex(44),
-
-    //     ..Default::default()
-    // });
-
-    // commands.entity(tilemap_entity).insert((
-    //     TilemapBundle {
-    //         grid_size,
-    //         map_type,
-    //         size: map_size,
-    //         storage: entity_tile_storage,
-    //         texture: TilemapTexture::Single(texture_handle),
-    //         tile_size: TilemapTileSize { x: 16.0, y: 16.0 },
-    //         transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 3.0)
-    //             * Transform::from_xyz(0., 0., 0.0),
-    //         // transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 3.0)
-    //         //     * Transform::from_xyz(0.0, 0.0, 0.0),
-    //         ..Default::default()
-    //     },
-    //     TileMapEntityLayer,
-    // ));
-
-    *game_state = State::new(GameState::PlayerTurn);
+    next_state.set(GameState::PlayerTurn);
 }
 
 pub fn setup_debug_layer(mut commands: Commands) {}
-
-pub fn audio_effects_setup(assets: Res<AssetServer>, mut commands: Commands) {
-    commands.spawn((
-        AudioBundle {
-            source: assets.load("sounds/steps-indoor-1.mp3"),
-            // source: assets.load("sounds/walking1_gravel-6133.mp3"),
-            ..Default::default()
-        },
-        WalkingAudioEffect::default(),
-    ));
-}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+
+use crate::{
+    events::StatusExpiredEvent, resources::RLTimeSystem, DamageAction, HealAction, StatusEffects,
+    StatusKind,
+};
+
+/// Applies the periodic effects and removes the expired statuses for every
+/// game time step elapsed since the last run. Runs when the player's turn
+/// starts, so running it again on resume does nothing.
+pub fn tick_status_effects(
+    time_system: Res<RLTimeSystem>,
+    mut effects_q: Query<(Entity, &mut StatusEffects)>,
+    mut expired_events: EventWriter<StatusExpiredEvent>,
+    mut last_time: Local<u32>,
+    mut commands: Commands,
+) {
+    let now = time_system.get_time();
+    // a new game starts a new clock, and undoing turns moves it back
+    if time_system.is_added() || now < *last_time {
+        *last_time = now;
+    }
+    if now <= *last_time {
+        return;
+    }
+
+    for time in (*last_time + 1)..=now {
+        for (entity, mut effects) in effects_q.iter_mut() {
+            if effects.effects.is_empty() {
+                continue;
+            }
+            for effect in effects.effects.iter_mut() {
+                let interval = match effect.kind.tick_interval() {
+                    Some(interval) => interval,
+                    None => continue,
+                };
+                while effect.next_tick <= time && effect.next_tick <= effect.expires_at {
+                    effect.next_tick += interval;
+                    match effect.kind {
+                        StatusKind::Poisoned | StatusKind::Burning => commands.add(DamageAction {
+                            entity,
+                            amount: effect.magnitude,
+                            source: effect.source,
+                        }),
+                        StatusKind::Regenerating => commands.add(HealAction {
+                            entity,
+                            amount: effect.magnitude,
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+
+            for kind in effects.expire(time) {
+                info!("{:?} is no longer {:?}", entity, kind);
+                expired_events.send(StatusExpiredEvent { entity, kind });
+            }
+        }
+    }
+    *last_time = now;
+}
@@ -0,0 +1,201 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ecs_tilemap::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    intentions::{IntentionSourceRef, RangedAttackIntention, UseItemIntention},
+    GameMap, Health, Monster, MyGameCamera, Player, RLAction,
+};
+
+use super::{
+    camera::CameraMode,
+    map_tile_info::{cursor_tile_pos, draw_tile_outline},
+};
+
+/// What the target is being picked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetingPurpose {
+    UseItem {
+        slot: usize,
+    },
+    /// shooting with the equipped ranged weapon
+    RangedAttack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetingRequest {
+    pub source: Entity,
+    pub purpose: TargetingPurpose,
+    /// max distance (in tiles) of the target from the source
+    pub range: u32,
+    pub cursor: TilePos,
+}
+
+/// Targeting mode: while a request is active the direction keys and the
+/// mouse move a cursor instead of the player.
+#[derive(Resource, Default, Debug)]
+pub struct Targeting {
+    pub request: Option<TargetingRequest>,
+}
+
+impl Targeting {
+    pub fn is_active(&self) -> bool {
+        self.request.is_some()
+    }
+
+    pub fn start(&mut self, request: TargetingRequest) {
+        info!("targeting: {:?}", request);
+        self.request = Some(request);
+    }
+
+    pub fn cancel(&mut self) {
+        self.request = None;
+    }
+}
+
+fn in_range(from: &TilePos, to: &TilePos, range: u32) -> bool {
+    from.x.abs_diff(to.x).max(from.y.abs_diff(to.y)) <= range
+}
+
+/// visible positions within `range` of `from`, closest first
+pub fn visible_targets(
+    map: &GameMap,
+    from: &TilePos,
+    range: u32,
+    positions: impl IntoIterator<Item = TilePos>,
+) -> Vec<TilePos> {
+    let distance = |pos: &TilePos| from.x.abs_diff(pos.x).max(from.y.abs_diff(pos.y));
+    let mut targets = positions
+        .into_iter()
+        .filter(|pos| pos != from && in_range(from, pos, range) && map.is_visible(pos))
+        .collect::<Vec<_>>();
+    targets.sort_by_key(|pos| (distance(pos), pos.x, pos.y));
+    targets
+}
+
+fn confirm_target(request: &TargetingRequest, commands: &mut Commands) {
+    match request.purpose {
+        TargetingPurpose::UseItem { slot } => {
+            commands.spawn(UseItemIntention {
+                slot,
+                source: IntentionSourceRef(request.source),
+                target: Some(request.cursor),
+            });
+        }
+        TargetingPurpose::RangedAttack => {
+            commands.spawn(RangedAttackIntention {
+                target: request.cursor,
+                source: IntentionSourceRef(request.source),
+            });
+        }
+    }
+}
+
+pub fn update_targeting(
+    mut targeting: ResMut<Targeting>,
+    player_q: Query<(&ActionState<RLAction>, &TilePos), With<Player>>,
+    monsters_q: Query<&TilePos, With<Monster>>,
+    actors_q: Query<&TilePos, (With<Health>, Without<Player>)>,
+    map: Res<GameMap>,
+    buttons: Res<Input<MouseButton>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MyGameCamera>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut lines: ResMut<DebugLines>,
+    mut last_mouse_tile: Local<Option<TilePos>>,
+    camera_mode: Res<CameraMode>,
+    mut commands: Commands,
+) {
+    let mut request = match targeting.request {
+        Some(request) => request,
+        None => return,
+    };
+    if *camera_mode == CameraMode::MapView {
+        return;
+    }
+    let (action, player_pos) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    if action.just_pressed(RLAction::Cancel) {
+        targeting.cancel();
+        return;
+    }
+
+    let mut dx = IVec2::default();
+    if action.just_pressed(RLAction::Up) {
+        dx.y += 1;
+    }
+    if action.just_pressed(RLAction::Down) {
+        dx.y -= 1;
+    }
+    if action.just_pressed(RLAction::Left) {
+        dx.x -= 1;
+    }
+    if action.just_pressed(RLAction::Right) {
+        dx.x += 1;
+    }
+
+    if action.just_pressed(RLAction::NextTarget) {
+        // cycles through the visible monsters, closest first
+        let targets = visible_targets(&map, player_pos, request.range, monsters_q.iter().copied());
+        let next = match targets.iter().position(|pos| *pos == request.cursor) {
+            Some(index) => targets.get(index + 1).or(targets.first()),
+            None => targets.first(),
+        };
+        if let Some(next) = next {
+            request.cursor = *next;
+        }
+    }
+
+    let cursor = IVec2::new(request.cursor.x as i32, request.cursor.y as i32) + dx;
+    if let Some(cursor) = map.checked_pos(cursor) {
+        if in_range(player_pos, &cursor, request.range) {
+            request.cursor = cursor;
+        }
+    }
+
+    // the mouse only takes over the cursor when it moves to another tile or clicks
+    let mut clicked = false;
+    if let (Ok((camera, camera_transform)), Ok(window)) =
+        (camera_q.get_single(), q_windows.get_single())
+    {
+        let mouse_tile = cursor_tile_pos(window, camera, camera_transform, &map);
+        let mouse_moved = mouse_tile != *last_mouse_tile;
+        *last_mouse_tile = mouse_tile;
+
+        if let Some(cursor) = mouse_tile {
+            let pressed = buttons.just_pressed(MouseButton::Left);
+            if (mouse_moved || pressed) && in_range(player_pos, &cursor, request.range) {
+                request.cursor = cursor;
+                clicked = pressed;
+            }
+        }
+    }
+
+    // the projectile stops at the first wall or actor on its way
+    let path = map.projectile_path(*player_pos, request.cursor, |pos| {
+        actors_q.iter().any(|actor| actor == pos)
+    });
+    if let Some(end) = path.last() {
+        let start = map.tile_to_world(player_pos).extend(1.);
+        let end = map.tile_to_world(end).extend(1.);
+        lines.line_colored(start, end, 0., Color::YELLOW);
+        for pos in path.iter().filter(|pos| **pos != request.cursor) {
+            draw_tile_outline(&mut lines, &map, pos, Color::YELLOW);
+        }
+    }
+
+    let valid = map.is_visible(&request.cursor)
+        && (request.purpose != TargetingPurpose::RangedAttack || !path.is_empty());
+    let color = if valid { Color::GREEN } else { Color::RED };
+    draw_tile_outline(&mut lines, &map, &request.cursor, color);
+
+    if valid && (clicked || action.just_pressed(RLAction::Confirm)) {
+        confirm_target(&request, &mut commands);
+        targeting.cancel();
+    } else {
+        targeting.request = Some(request);
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use bevy_tweening::Animator;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    intentions::{DoorIntention, IntentionSourceRef, MoveIntention},
+    resources::RLTimeSystem,
+    DoorState, Experience, GameMap, LogCategory, LogMessage, Monster, Player, RLAction,
+};
+
+use super::{targeting::Targeting, update::TURN_ACTIONS};
+
+/// Travel
+///
+/// Walks the player along a known path, one step per move animation. The
+/// travel stops on any key press, when a new monster comes into view or
+/// when a step doesn't go as planned.
+#[derive(Resource, Default, Debug)]
+pub struct Travel {
+    path: VecDeque<TilePos>,
+    /// monsters already in sight when the travel started
+    seen: Vec<Entity>,
+    /// tile the player should be standing on after the last step
+    expected: Option<TilePos>,
+}
+
+impl Travel {
+    /// `path` starts on the tile of the player, as returned by `GameMap::find_path`
+    pub fn start(&mut self, path: Vec<TilePos>, seen: Vec<Entity>) {
+        self.path = path.into_iter().skip(1).collect();
+        self.seen = seen;
+        self.expected = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.path.clear();
+        self.seen.clear();
+        self.expected = None;
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.path.is_empty()
+    }
+
+    /// steps left to walk
+    pub fn path(&self) -> impl Iterator<Item = &TilePos> {
+        self.path.iter()
+    }
+}
+
+type TravelerQueryData = (
+    Entity,
+    &'static TilePos,
+    &'static Transform,
+    &'static ActionState<RLAction>,
+    &'static Experience,
+    Option<&'static Animator<Transform>>,
+);
+pub fn update_travel(
+    mut travel: ResMut<Travel>,
+    player_q: Query<TravelerQueryData, With<Player>>,
+    monsters_q: Query<(Entity, &TilePos), With<Monster>>,
+    intentions_q: Query<(), Or<(With<MoveIntention>, With<DoorIntention>)>>,
+    targeting: Res<Targeting>,
+    map: Res<GameMap>,
+    time_system: Res<RLTimeSystem>,
+    mut commands: Commands,
+) {
+    if !travel.is_active() {
+        return;
+    }
+    let (e, tile_pos, transform, action, experience, animator) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    if TURN_ACTIONS.iter().any(|a| action.just_pressed(*a))
+        || targeting.is_active()
+        || experience.pending_level_ups > 0
+    {
+        travel.stop();
+        return;
+    }
+    let spotted = monsters_q
+        .iter()
+        .any(|(monster, pos)| map.is_visible(pos) && !travel.seen.contains(&monster));
+    if spotted {
+        travel.stop();
+        commands.add(LogMessage::new(
+            LogCategory::Info,
+            "you stop: a monster comes into view",
+        ));
+        return;
+    }
+
+    // waits for the previous step to be over, slow terrain taking turns
+    let moving = animator.map_or(false, |animator| animator.tweenable().progress() < 1.);
+    if moving || !intentions_q.is_empty() || !time_system.is_ready(e) {
+        return;
+    }
+    if travel
+        .expected
+        .map_or(false, |expected| expected != *tile_pos)
+    {
+        info!("travel interrupted at {:?}", tile_pos);
+        travel.stop();
+        return;
+    }
+
+    let next = match travel.path.front() {
+        Some(next) => *next,
+        None => return,
+    };
+    if monsters_q.iter().any(|(_, pos)| *pos == next) {
+        travel.stop();
+        commands.add(LogMessage::new(
+            LogCategory::Blocked,
+            "something is in the way",
+        ));
+        return;
+    }
+    if map.door(&next) == Some(DoorState::Closed) {
+        commands.spawn(DoorIntention {
+            target: next,
+            source: IntentionSourceRef(e),
+            open: true,
+        });
+        return;
+    }
+
+    travel.path.pop_front();
+    travel.expected = Some(next);
+    commands.spawn(MoveIntention {
+        target: next,
+        source: IntentionSourceRef(e),
+        target_position: map.tile_to_world(&next).extend(transform.translation.z),
+    });
+}
@@ -1,14 +1,19 @@
-use bevy::{core_pipeline::clear_color::ClearColorConfig, prelude::*, render::view::RenderLayers};
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig, prelude::*, render::view::RenderLayers,
+    window::PrimaryWindow,
+};
 use bevy_ecs_tilemap::tiles::TilePos;
 use bevy_inspector_egui::egui::Margin;
 
 use crate::{
-    events::{TileInfoEvent, TurnEndEvent},
-    resources::RLTimeSystem,
-    ButtonStatus, GameUiCamera, Monster, MyAssets, MyGameCamera, Player, PlayerPositionUILabel,
-    TileInfoUI, TimeUIButton, TimeUIField,
+    describe, events::TurnEndEvent, resources::RLTimeSystem, Attack, ButtonStatus, CharacterUI,
+    Defense, EffectiveStats, Equipment, GameEntity, GameLogPanel, GameLogUI, GameMap, GameUiCamera,
+    Health, HoveredTile, Inventory, InventoryUI, Item, ItemTable, MyAssets, Player, StatusEffects,
+    TileInfoUI, TileTooltipUI, TimeUIButton, TimeUIField,
 };
 
+use super::camera::GAME_VIEW_PERCENT;
+
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
@@ -34,26 +39,30 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
         GameUiCamera {},
         UiCameraConfig { show_ui: true },
         RenderLayers::from_layers(&[1]),
+        GameEntity,
     ));
 
     commands
-        .spawn(NodeBundle {
-            style: Style {
-                // fill the entire window
-                flex_grow: 1.0,
-                flex_direction: FlexDirection::Column,
-                align_items: AlignItems::FlexStart,
-                left: Val::Percent(70.),
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    // the side panel, right of the game view
+                    width: Val::Percent(100. - GAME_VIEW_PERCENT),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::FlexStart,
+                    left: Val::Percent(GAME_VIEW_PERCENT),
 
-                margin: UiRect {
-                    left: Val::Px(10.),
+                    padding: UiRect {
+                        left: Val::Px(10.),
+                        ..Default::default()
+                    },
                     ..Default::default()
                 },
+                background_color: BackgroundColor(bg_color),
                 ..Default::default()
             },
-            background_color: BackgroundColor(bg_color),
-            ..Default::default()
-        })
+            GameEntity,
+        ))
         .with_children(|builder| {
             builder
                 .spawn(NodeBundle {
@@ -70,46 +79,7 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
                     ..Default::default()
                 })
                 .with_children(|builder| {
-                    text_row(
-                        builder,
-                        "Hello, world!",
-                        TextStyle {
-                            font: assets.ui_font.clone(),
-                            font_size: 48.0,
-                            color: text_color,
-                        },
-                    );
-                    text_row(
-                        builder,
-                        "Hello, world data!",
-                        TextStyle {
-                            font: assets.ui_font.clone(),
-                            font_size: 28.0,
-                            color: data_color,
-                        },
-                    );
-
-                    builder.spawn((
-                        TextBundle::from_sections([
-                            TextSection::new(
-                                "player position: 0, 0",
-                                TextStyle {
-                                    font: assets.ui_font.clone(),
-                                    font_size: 22.0,
-                                    color: data_color,
-                                },
-                            ),
-                            TextSection::new(
-                                "Hello, ",
-                                TextStyle {
-                                    font: assets.ui_font.clone(),
-                                    font_size: 22.0,
-                                    color: data_color,
-                                },
-                            ),
-                        ]),
-                        PlayerPositionUILabel::default(),
-                    ));
+                    spawn_hud(builder, &assets);
                     builder.spawn((
                         TextBundle::from_section(
                             "time: 00:00:00",
@@ -146,7 +116,7 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
                             parent.spawn((TextBundle {
                                 text: Text {
                                     sections: vec![TextSection {
-                                        value: "Button".to_string(),
+                                        value: "end turn".to_string(),
                                         style: TextStyle {
                                             font: assets.ui_font.clone(),
                                             font_size: 22.0,
@@ -161,7 +131,7 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
                     builder.spawn((
                         TextBundle::from_sections([
                             TextSection::new(
-                                "player position: 0, 0",
+                                "",
                                 TextStyle {
                                     font: assets.ui_font.clone(),
                                     font_size: 22.0,
@@ -169,7 +139,7 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
                                 },
                             ),
                             TextSection::new(
-                                "Hello, ",
+                                "",
                                 TextStyle {
                                     font: assets.ui_font.clone(),
                                     font_size: 22.0,
@@ -179,6 +149,96 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
                         ]),
                         TileInfoUI::default(),
                     ));
+                    builder.spawn((
+                        TextBundle::from_sections([
+                            TextSection::new(
+                                "inventory",
+                                TextStyle {
+                                    font: assets.ui_font.clone(),
+                                    font_size: 28.0,
+                                    color: text_color,
+                                },
+                            ),
+                            TextSection::new(
+                                "",
+                                TextStyle {
+                                    font: assets.ui_font.clone(),
+                                    font_size: 22.0,
+                                    color: data_color,
+                                },
+                            ),
+                        ])
+                        .with_style(Style {
+                            margin: UiRect {
+                                top: Val::Px(10.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        }),
+                        InventoryUI::default(),
+                    ));
+                    builder.spawn((
+                        TextBundle::from_sections([
+                            TextSection::new(
+                                "character",
+                                TextStyle {
+                                    font: assets.ui_font.clone(),
+                                    font_size: 28.0,
+                                    color: text_color,
+                                },
+                            ),
+                            TextSection::new(
+                                "",
+                                TextStyle {
+                                    font: assets.ui_font.clone(),
+                                    font_size: 22.0,
+                                    color: data_color,
+                                },
+                            ),
+                        ])
+                        .with_style(Style {
+                            margin: UiRect {
+                                top: Val::Px(10.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        }),
+                        CharacterUI::default(),
+                    ));
+                    builder
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    justify_content: JustifyContent::FlexEnd,
+                                    margin: UiRect {
+                                        top: Val::Px(10.),
+                                        ..Default::default()
+                                    },
+                                    width: Val::Percent(100.),
+                                    height: Val::Px(220.),
+                                    overflow: Overflow::clip_y(),
+                                    ..Default::default()
+                                },
+                                background_color: BackgroundColor(Color::NONE),
+                                ..Default::default()
+                            },
+                            Interaction::default(),
+                            GameLogPanel::default(),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: assets.ui_font.clone(),
+                                        font_size: 18.0,
+                                        color: data_color,
+                                    },
+                                ),
+                                GameLogUI::default(),
+                            ));
+                        });
                 });
             // builder.spawn(NodeBundle {
             //     style: Style {
@@ -195,6 +255,30 @@ pub fn game_ui_setup(mut commands: Commands, assets: Res<MyAssets>) {
             //     ..Default::default()
             // });
         });
+
+    // tooltip following the cursor, filled by `ui_update_on_query_tile_event`
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(5),
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: assets.ui_font.clone(),
+                    font_size: 18.0,
+                    color: data_color,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(4.)),
+                ..Default::default()
+            })
+            .with_background_color(bg_color)
+        },
+        TileTooltipUI::default(),
+        GameEntity,
+    ));
 }
 
 fn text_row(builder: &mut ChildBuilder, text: &str, style: TextStyle) {
@@ -240,101 +324,250 @@ pub fn game_ui_interaction(
     }
 }
 
-pub fn game_ui_update(
-    mut query: Query<&mut Text, With<TimeUIField>>,
-    rl_time: Res<RLTimeSystem>,
-    // mut player_position_query: Query<(&Transform), (With<Player>)>,
-    // mut player_position_label_query: Query<(&mut Text), (With<PlayerPositionUILabel>)>,
-    btn_query: Query<(&ButtonStatus), (With<TimeUIButton>)>,
-) {
-    let btn_status = match btn_query.get_single() {
-        Ok(btn) => btn,
-        Err(_) => return,
-    };
+/// Shows the `RLTimeSystem` clock, only when the time changes.
+pub fn game_ui_update(mut query: Query<&mut Text, With<TimeUIField>>, rl_time: Res<RLTimeSystem>) {
+    if !rl_time.is_changed() {
+        return;
+    }
     let mut text = match query.get_single_mut() {
-        Ok(btn) => btn,
+        Ok(text) => text,
         Err(_) => return,
     };
 
-    // let mut player_position_text = match player_position_label_query.get_single_mut() {
-    //     Ok(position_label) => position_label,
-    //     Err(_) => return,
-    // };
+    text.sections[0].value = format!("time {}", *rl_time);
+}
 
-    text.sections[0].value = format!("{} ({:?})", *rl_time, btn_status);
-    // player_position_text.sections[0].value = format!(
-    //     "player position: {:?}",
-    //     player_position_query
-    //         .get_single_mut()
-    //         .map(|transform| transform.translation.truncate())
-    // );
+/// `[#####-----]` like bar of `width` characters
+fn health_bar(current: i32, max: i32, width: usize) -> String {
+    let filled = if max > 0 {
+        ((current.max(0) as usize * width) / max as usize).min(width)
+    } else {
+        0
+    };
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled))
 }
 
+type InspectedActorData = (
+    Option<&'static Name>,
+    Option<&'static Player>,
+    &'static TilePos,
+    &'static Health,
+    &'static EffectiveStats,
+    Option<&'static StatusEffects>,
+);
+
+/// Lines describing what the player knows about `tile`: terrain always,
+/// actors and items only while the tile is in sight.
+fn inspect_tile(
+    tile: &TilePos,
+    map: &GameMap,
+    actors_q: &Query<InspectedActorData>,
+    items_q: &Query<(&Item, &TilePos)>,
+    table: &ItemTable,
+) -> Vec<String> {
+    let terrain = map.terrain(tile);
+    let state = if map.is_visible(tile) {
+        "in sight"
+    } else {
+        "remembered"
+    };
+    let mut lines = vec![format!("{} ({})", terrain.name, state)];
+    let mut properties = Vec::new();
+    if !terrain.walkable {
+        properties.push("blocks movement".to_string());
+    }
+    if !terrain.transparent {
+        properties.push("blocks sight".to_string());
+    }
+    if terrain.movement_cost > 1 {
+        properties.push(format!("cost {}", terrain.movement_cost));
+    }
+    if !properties.is_empty() {
+        lines.push(properties.join(", "));
+    }
+
+    if !map.is_visible(tile) {
+        return lines;
+    }
+    for (name, player, _, health, stats, effects) in actors_q.iter().filter(|a| a.2 == tile) {
+        let mut line = format!(
+            "{} {} {}/{}",
+            describe(name, player.is_some()),
+            health_bar(health.current, stats.max_health, 10),
+            health.current,
+            stats.max_health
+        );
+        if let Some(effects) = effects.filter(|e| !e.effects.is_empty()) {
+            let kinds = effects
+                .effects
+                .iter()
+                .map(|effect| format!("{:?}", effect.kind).to_lowercase())
+                .collect::<Vec<_>>();
+            line.push_str(&format!(" ({})", kinds.join(", ")));
+        }
+        lines.push(line);
+    }
+    for (item, _) in items_q.iter().filter(|(_, pos)| *pos == tile) {
+        let name = table
+            .get(&item.id)
+            .map_or(item.id.as_str(), |item| item.name.as_str());
+        if item.count > 1 {
+            lines.push(format!("{} x{}", name, item.count));
+        } else {
+            lines.push(name.to_string());
+        }
+    }
+    lines
+}
+
+/// Shows the hovered tile in the side panel and in a tooltip next to the
+/// cursor. Tiles never seen are not inspected.
 pub fn ui_update_on_query_tile_event(
-    mut tile_info_event: EventReader<TileInfoEvent>,
-    mut tile_info_ui: Query<&mut Text, With<TileInfoUI>>,
-    monsters_q: Query<(&TilePos, &Name), With<crate::Monster>>,
-    player_q: Query<(&TilePos), (With<Player>, Without<Monster>)>,
+    hovered: Res<HoveredTile>,
+    mut tile_info_ui: Query<&mut Text, (With<TileInfoUI>, Without<TileTooltipUI>)>,
+    mut tooltip_ui: Query<(&mut Text, &mut Style, &mut Visibility), With<TileTooltipUI>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    actors_q: Query<InspectedActorData>,
+    items_q: Query<(&Item, &TilePos)>,
+    table: Res<ItemTable>,
+    map: Option<Res<GameMap>>,
 ) {
     let mut tile_info_text = match tile_info_ui.get_single_mut() {
         Ok(tile_info_text) => tile_info_text,
         Err(_) => return,
     };
+    let (mut tooltip_text, mut tooltip_style, mut tooltip_visibility) =
+        match tooltip_ui.get_single_mut() {
+            Ok(tooltip) => tooltip,
+            Err(_) => return,
+        };
 
-    for event in tile_info_event.iter() {
-        println!("TileInfoEvent: {:?}", event);
-        let tile = event.tile_pos;
-        let monsters_at_tile = monsters_q
-            .iter()
-            .filter(|(pos, _)| **pos == tile)
-            .collect::<Vec<_>>();
-        if !monsters_at_tile.is_empty() {
-            tile_info_text.sections[0].value = format!(
-                "monsters: {:?}",
-                monsters_at_tile
-                    .iter()
-                    .map(|(_, name)| format!("{:?}", name))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );
-        }
+    let known_tile = match (hovered.tile, map.as_ref()) {
+        (Some(tile), Some(map)) if map.is_visited(&tile) => Some((tile, map)),
+        _ => None,
+    };
+    let (header, lines) = match known_tile {
+        Some((tile, map)) => (
+            format!("tile {}, {}", tile.x, tile.y),
+            inspect_tile(&tile, map, &actors_q, &items_q, &table),
+        ),
+        None => (String::new(), Vec::new()),
+    };
 
-        let player_at_tile = player_q
-            .iter()
-            .filter(|pos| **pos == tile)
-            .collect::<Vec<_>>();
+    // only touch the texts when they change, to avoid relayouts every frame
+    let details = lines
+        .iter()
+        .map(|line| format!("\n{}", line))
+        .collect::<String>();
+    if tile_info_text.sections[0].value != header {
+        tile_info_text.sections[0].value = header;
+    }
+    if tile_info_text.sections[1].value != details {
+        tile_info_text.sections[1].value = details;
+    }
 
-        if !player_at_tile.is_empty() {
-            tile_info_text.sections[0].value = format!("player: {:?}", player_at_tile);
+    let tooltip = lines.join("\n");
+    if tooltip_text.sections[0].value != tooltip {
+        tooltip_text.sections[0].value = tooltip;
+    }
+    let cursor = q_windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+    match cursor.filter(|_| !lines.is_empty()) {
+        Some(cursor) => {
+            let (left, top) = (Val::Px(cursor.x + 16.), Val::Px(cursor.y + 16.));
+            if tooltip_style.left != left || tooltip_style.top != top {
+                tooltip_style.left = left;
+                tooltip_style.top = top;
+            }
+            if *tooltip_visibility != Visibility::Visible {
+                *tooltip_visibility = Visibility::Visible;
+            }
+        }
+        None => {
+            if *tooltip_visibility != Visibility::Hidden {
+                *tooltip_visibility = Visibility::Hidden;
+            }
         }
     }
-    tile_info_event.clear();
 }
 
-pub fn game_ui_player_position_update(
-    player_position_query: Query<(&Transform, &GlobalTransform, &TilePos), With<Player>>,
-    mut player_position_label_query: Query<&mut Text, With<PlayerPositionUILabel>>,
-    camera_q: Query<(&GlobalTransform, &Camera), (With<MyGameCamera>, Without<Player>)>,
+pub fn game_ui_inventory_update(
+    inventory_q: Query<&Inventory, (With<Player>, Changed<Inventory>)>,
+    mut inventory_ui: Query<&mut Text, With<InventoryUI>>,
+    table: Res<ItemTable>,
 ) {
-    let mut player_position_text = match player_position_label_query.get_single_mut() {
-        Ok(position_label) => position_label,
+    let inventory = match inventory_q.get_single() {
+        Ok(inventory) => inventory,
+        Err(_) => return,
+    };
+    let mut text = match inventory_ui.get_single_mut() {
+        Ok(text) => text,
         Err(_) => return,
     };
 
-    if let Ok((transform, global_transform, tile_pos)) = player_position_query.get_single() {
-        player_position_text.sections[0].value = format!(
-            "player position: {:?} [ tile: {:?}] global: {:?}",
-            transform.translation.truncate(),
-            tile_pos,
-            global_transform.translation().truncate()
-        );
-    }
+    text.sections[0].value = format!(
+        "inventory {}/{} ({:.1} kg)",
+        inventory.stacks.len(),
+        inventory.capacity,
+        inventory.weight(&table)
+    );
+    text.sections[1].value = inventory
+        .stacks
+        .iter()
+        .enumerate()
+        .map(|(slot, stack)| {
+            let name = table
+                .get(&stack.id)
+                .map_or(stack.id.as_str(), |item| item.name.as_str());
+            let cursor = if slot == inventory.selected { ">" } else { " " };
+            format!("\n{} {} x{}", cursor, name, stack.count)
+        })
+        .collect::<String>();
+}
 
-    if let Ok((global_transform, camera)) = camera_q.get_single() {
-        player_position_text.sections[1].value = format!(
-            "camnera position: {:?} [ camera: {:?}]",
-            global_transform.translation().truncate(),
-            camera
-        );
+type CharacterQueryData = (
+    &'static Health,
+    &'static Attack,
+    &'static Defense,
+    &'static EffectiveStats,
+    &'static Equipment,
+);
+
+/// Shows base and effective stats (base + equipment) of the player.
+pub fn game_ui_character_update(
+    player_q: Query<
+        CharacterQueryData,
+        (With<Player>, Or<(Changed<EffectiveStats>, Changed<Health>)>),
+    >,
+    mut character_ui: Query<&mut Text, With<CharacterUI>>,
+    table: Res<ItemTable>,
+) {
+    let (health, attack, defense, stats, equipment) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let mut text = match character_ui.get_single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+
+    let mut value = format!(
+        "\nhealth {}/{} (base {})\nattack {} (base {})\ndefense {} (base {})",
+        health.current,
+        stats.max_health,
+        health.max,
+        stats.attack,
+        attack.damage,
+        stats.defense,
+        defense.value
+    );
+    for (slot, item) in equipment.items.iter() {
+        let name = table
+            .get(&item.id)
+            .map_or(item.id.as_str(), |item| item.name.as_str());
+        value.push_str(&format!("\n{:?}: {}", slot, name));
     }
+    text.sections[1].value = value;
 }
@@ -1,21 +1,41 @@
 use bevy::{prelude::*, utils::HashSet};
-use bevy_ecs_tilemap::{
-    prelude::{get_tilemap_center_transform, TilemapGridSize, TilemapId, TilemapSize, TilemapType},
-    tiles::{TilePos, TileStorage, TileTextureIndex},
-};
+use bevy_ecs_tilemap::tiles::TilePos;
 use leafwing_input_manager::prelude::*;
 
 use crate::{
-    algorithms::tile_pos_to_world_pos,
     bresenham_line,
     events::TurnEndEvent,
-    intentions::{AttackIntention, IntentionSourceRef, MoveIntention},
+    intentions::{
+        AttackIntention, DoorIntention, DropIntention, EquipIntention, IntentionSourceRef,
+        MoveIntention, PickUpIntention, UnequipIntention, UseItemIntention,
+    },
     resources::RLTimeSystem,
-    FovOccluder, GameState, IntentionKind, IntentionSourceId, IsVisited, Monster, MyGameCamera,
-    NeedsFovUpdate, Player, RLAction, TileKind, TileMapEntityLayer, TileMapLayer0,
-    TileMapVisibilityLayer, VisibleTiles, VisitedTiles, Wall,
+    DoorState, Equipment, Experience, GameMap, GameState, Inventory, Item, ItemTable, LogCategory,
+    LogMessage, Monster, NeedsFovUpdate, Player, RLAction, StatusEffects, StatusKind,
+};
+
+use super::{
+    camera::CameraMode,
+    console::DebugFlags,
+    debug_overlay::FovDebug,
+    targeting::{visible_targets, Targeting, TargetingPurpose, TargetingRequest},
 };
-use bevy_prototype_debug_lines::*;
+
+/// actions taking the player's turn
+pub(super) const TURN_ACTIONS: [RLAction; 12] = [
+    RLAction::Up,
+    RLAction::Down,
+    RLAction::Left,
+    RLAction::Right,
+    RLAction::OpenDoor,
+    RLAction::CloseDoor,
+    RLAction::PickUp,
+    RLAction::Drop,
+    RLAction::UseItem,
+    RLAction::Equip,
+    RLAction::Unequip,
+    RLAction::Fire,
+];
 
 type PlayerUpdateQueryData = (
     Entity,
@@ -23,165 +43,299 @@ type PlayerUpdateQueryData = (
     &'static ActionState<RLAction>,
     &'static mut Player,
     &'static mut TilePos,
+    &'static mut Inventory,
+    &'static Equipment,
 );
 pub fn update_player(
     mut q: Query<PlayerUpdateQueryData, With<Player>>,
-    mut tiles_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType), With<TileMapLayer0>>,
+    map: Res<GameMap>,
     //world: &World,
-    walls_q: Query<(Entity, &TilePos), (With<Wall>, Without<Player>)>,
-    monsters_q: Query<(Entity, &TilePos), (With<Monster>, Without<Wall>, Without<Player>)>,
+    monsters_q: Query<(Entity, &TilePos), (With<Monster>, Without<Player>)>,
+    items_q: Query<(Entity, &TilePos), (With<Item>, Without<Player>)>,
+    table: Res<ItemTable>,
+    mut targeting: ResMut<Targeting>,
+    status_q: Query<(&StatusEffects, &Experience), With<Player>>,
+    time_system: Res<RLTimeSystem>,
+    mut end_turn_ew: EventWriter<TurnEndEvent>,
+    camera_mode: Res<CameraMode>,
     mut commands: Commands,
 ) {
     // info!("update_player");
-    if let Ok((map_size, grid_size, map_type)) = tiles_q.get_single_mut() {
-        if let Ok((e, transform, action, mut _player, tile_position)) = q.get_single_mut() {
-            // println!("Player tile pos: {:?}", player.tile_pos);
-
-            let mut dx = IVec2::default();
-            if action.just_pressed(RLAction::Up) {
-                // transform.translation.y += 10.;
-                dx.y += 1;
-            }
-            if action.just_pressed(RLAction::Down) {
-                // transform.translation.y -= 10.;
-                dx.y -= 1;
+    if targeting.is_active() || *camera_mode == CameraMode::MapView {
+        return;
+    }
+
+    if let Ok((e, transform, action, mut _player, tile_position, mut inventory, equipment)) =
+        q.get_single_mut()
+    {
+        let (effects, experience) = match status_q.get_single() {
+            Ok(status) => status,
+            Err(_) => return,
+        };
+        // the level-up screen has to be closed first
+        if experience.pending_level_ups > 0 {
+            return;
+        }
+
+        // the last move takes its time: the turns go by until it is over
+        if !time_system.is_ready(e) {
+            end_turn_ew.send(TurnEndEvent);
+            return;
+        }
+
+        // a stunned player loses the turn whatever they try to do
+        let is_stunned = effects.has(StatusKind::Stunned);
+        if is_stunned && TURN_ACTIONS.iter().any(|a| action.just_pressed(*a)) {
+            info!("the player is stunned");
+            commands.add(LogMessage::new(LogCategory::Status, "you are stunned"));
+            end_turn_ew.send(TurnEndEvent);
+            return;
+        }
+
+        // println!("Player tile pos: {:?}", player.tile_pos);
+
+        if action.just_pressed(RLAction::PickUp) {
+            match items_q.iter().find(|(_, pos)| **pos == *tile_position) {
+                Some((item, _)) => {
+                    commands.spawn(PickUpIntention {
+                        item,
+                        source: IntentionSourceRef(e),
+                    });
+                }
+                None => {
+                    commands.add(LogMessage::new(
+                        LogCategory::Info,
+                        "nothing to pick up here",
+                    ));
+                }
             }
-            if action.just_pressed(RLAction::Left) {
-                // transform.translation.x -= 10.;
-                dx.x -= 1;
+            return;
+        }
+
+        if action.just_pressed(RLAction::Drop) {
+            commands.spawn(DropIntention {
+                slot: inventory.selected,
+                source: IntentionSourceRef(e),
+            });
+            return;
+        }
+
+        if action.just_pressed(RLAction::NextItem) {
+            inventory.select_next();
+            return;
+        }
+
+        // aims at the closest visible monster, if any
+        let nearest_target = |range: u32| {
+            let monsters = monsters_q.iter().map(|(_, pos)| *pos);
+            visible_targets(&map, &tile_position, range, monsters)
+                .first()
+                .copied()
+                .unwrap_or(*tile_position)
+        };
+
+        if action.just_pressed(RLAction::Fire) {
+            match equipment.ranged_weapon_range(&table) {
+                Some(range) => targeting.start(TargetingRequest {
+                    source: e,
+                    purpose: TargetingPurpose::RangedAttack,
+                    range,
+                    cursor: nearest_target(range),
+                }),
+                None => {
+                    commands.add(LogMessage::new(
+                        LogCategory::Info,
+                        "no ranged weapon equipped",
+                    ));
+                }
             }
+            return;
+        }
 
-            if action.just_pressed(RLAction::Right) {
-                // transform.translation.x += 10.;
-                dx.x += 1;
+        if action.just_pressed(RLAction::UseItem) {
+            let slot = inventory.selected;
+            match inventory
+                .selected_stack()
+                .and_then(|stack| table.get(&stack.id))
+            {
+                Some(definition) => {
+                    match definition
+                        .use_effect
+                        .and_then(|effect| effect.target_range())
+                    {
+                        Some(range) => targeting.start(TargetingRequest {
+                            source: e,
+                            purpose: TargetingPurpose::UseItem { slot },
+                            range,
+                            cursor: nearest_target(range),
+                        }),
+                        None => {
+                            commands.spawn(UseItemIntention {
+                                slot,
+                                source: IntentionSourceRef(e),
+                                target: None,
+                            });
+                        }
+                    }
+                }
+                None => info!("nothing to use"),
             }
+            return;
+        }
 
-            if dx.length_squared() == 0 {
-                return;
+        if action.just_pressed(RLAction::Equip) {
+            commands.spawn(EquipIntention {
+                slot: inventory.selected,
+                source: IntentionSourceRef(e),
+            });
+            return;
+        }
+
+        if action.just_pressed(RLAction::Unequip) {
+            // takes off the most recently worn item
+            match equipment.items.len().checked_sub(1) {
+                Some(index) => {
+                    commands.spawn(UnequipIntention {
+                        index,
+                        source: IntentionSourceRef(e),
+                    });
+                }
+                None => info!("nothing equipped"),
             }
+            return;
+        }
 
-            let desired_pos = IVec2::new(tile_position.x as i32, tile_position.y as i32) + dx;
-            if desired_pos.length_squared() > 0 {
-                info!("desired_pos: {:?}", desired_pos);
+        for (door_action, open) in [(RLAction::OpenDoor, true), (RLAction::CloseDoor, false)] {
+            if !action.just_pressed(door_action) {
+                continue;
             }
 
-            let pos = match tile_pos_to_world_pos(
-                &TilePos::new(desired_pos.x as u32, desired_pos.y as u32),
-                map_size,
-                grid_size,
-                map_type,
-            ) {
-                Some(pos) => pos,
-                None => {
-                    warn!("Player outside of map");
-                    return;
-                }
-            };
-
-            let monsters = monsters_q
-                .iter()
-                .filter(|(_, monster_tile_pos)| {
-                    let monster_tile_pos = *monster_tile_pos;
-                    monster_tile_pos.x == desired_pos.x as u32
-                        && monster_tile_pos.y == desired_pos.y as u32
-                })
-                .collect::<Vec<_>>(); // mosters at desired position
-
-            if !monsters.is_empty() {
-                info!("Player wants to attack monster");
-                for (monster_e, _) in monsters {
-                    // commands.entity(monster_e).despawn_recursive();
-                    commands.spawn(AttackIntention {
-                        target: IntentionSourceRef(monster_e),
+            let player_cell = IVec2::new(tile_position.x as i32, tile_position.y as i32);
+            let door = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+                .into_iter()
+                .filter_map(|d| map.checked_pos(player_cell + d))
+                .find(|pos| match map.door(pos) {
+                    Some(DoorState::Open) => !open,
+                    Some(_) => open,
+                    None => false,
+                });
+
+            match door {
+                Some(target) => {
+                    commands.spawn(DoorIntention {
+                        target,
                         source: IntentionSourceRef(e),
-                        target_pos: TilePos::new(desired_pos.x as u32, desired_pos.y as u32),
+                        open,
                     });
                 }
+                None => info!("no door to {} nearby", if open { "open" } else { "close" }),
+            }
+            return;
+        }
+
+        let mut dx = IVec2::default();
+        if action.just_pressed(RLAction::Up) {
+            // transform.translation.y += 10.;
+            dx.y += 1;
+        }
+        if action.just_pressed(RLAction::Down) {
+            // transform.translation.y -= 10.;
+            dx.y -= 1;
+        }
+        if action.just_pressed(RLAction::Left) {
+            // transform.translation.x -= 10.;
+            dx.x -= 1;
+        }
+
+        if action.just_pressed(RLAction::Right) {
+            // transform.translation.x += 10.;
+            dx.x += 1;
+        }
+
+        if dx.length_squared() == 0 {
+            return;
+        }
+
+        let desired_pos = IVec2::new(tile_position.x as i32, tile_position.y as i32) + dx;
+        if desired_pos.length_squared() > 0 {
+            info!("desired_pos: {:?}", desired_pos);
+        }
+
+        let pos = match map.checked_pos(desired_pos) {
+            Some(tile_pos) => map.tile_to_world(&tile_pos),
+            None => {
+                warn!("Player outside of map");
                 return;
             }
+        };
 
-            let target_position = Vec3::new(
-                // (pos.x as f32 + 0.5 * grid_size.x) as f32,
-                // (pos.y as f32 + 0.5 * grid_size.y) as f32,
-                pos.x as f32,
-                pos.y as f32,
-                transform.translation.z,
-            );
-            info!(
-                "MoveIntention: {:?} wants to move to {:?}",
-                e, target_position
-            );
-            commands.spawn((
-                MoveIntention {
-                    target: TilePos::new(desired_pos.x as u32, desired_pos.y as u32),
+        let monsters = monsters_q
+            .iter()
+            .filter(|(_, monster_tile_pos)| {
+                let monster_tile_pos = *monster_tile_pos;
+                monster_tile_pos.x == desired_pos.x as u32
+                    && monster_tile_pos.y == desired_pos.y as u32
+            })
+            .collect::<Vec<_>>(); // mosters at desired position
+
+        if !monsters.is_empty() {
+            info!("Player wants to attack monster");
+            for (monster_e, _) in monsters {
+                // commands.entity(monster_e).despawn_recursive();
+                commands.spawn(AttackIntention {
+                    target: IntentionSourceRef(monster_e),
                     source: IntentionSourceRef(e),
-                    target_position,
-                },
-                // IntentionSourceRef(e.0),
-            ));
+                    target_pos: TilePos::new(desired_pos.x as u32, desired_pos.y as u32),
+                });
+            }
+            return;
         }
+
+        let target_position = Vec3::new(
+            // (pos.x as f32 + 0.5 * grid_size.x) as f32,
+            // (pos.y as f32 + 0.5 * grid_size.y) as f32,
+            pos.x,
+            pos.y,
+            transform.translation.z,
+        );
+        info!(
+            "MoveIntention: {:?} wants to move to {:?}",
+            e, target_position
+        );
+        commands.spawn((
+            MoveIntention {
+                target: TilePos::new(desired_pos.x as u32, desired_pos.y as u32),
+                source: IntentionSourceRef(e),
+                target_position,
+            },
+            // IntentionSourceRef(e.0),
+        ));
     }
 }
 
-type Layer0OccludedTilesFilter = (
-    With<TileMapLayer0>,
-    // With<FovOccluder>,
-    Without<TileMapVisibilityLayer>,
-);
-
 pub fn update_visibile_tiles(
-    mut player_q: Query<(Entity, &Transform, &mut Player), (With<Player>, With<NeedsFovUpdate>)>,
-    // layer0_q: Query<(&mut IsVisited, &TileStorage, &TilemapSize), With<TileMapLayer0>>,
-    // mut fov_occluder_layer_q: Query<(&mut TileStorage,), Layer0OccludedTilesFilter>,
-    fov_occluder_tiles_q: Query<(&TilePos), (With<TilemapId>, With<FovOccluder>)>,
-
-    mut visibility_layer_q: Query<
-        (
-            &mut TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-        ),
-        With<TileMapVisibilityLayer>,
-    >,
-
+    mut player_q: Query<(Entity, &TilePos, &mut Player), (With<Player>, With<NeedsFovUpdate>)>,
+    mut map: ResMut<GameMap>,
+    debug_flags: Res<DebugFlags>,
+    mut fov_debug: ResMut<FovDebug>,
     mut commands: Commands,
 ) {
     // println!("update_visibile_tiles");
-    let (player_entity, transform, mut player) = match player_q.get_single_mut() {
+    let (player_entity, player_cell, mut player) = match player_q.get_single_mut() {
         Ok(player) => player,
         Err(_) => {
             return;
         }
     };
-
-    let player_position = Vec2::new(transform.translation.x, transform.translation.y);
-    let (visible_tiles_storage, size, grid_size, map_type) =
-        match visibility_layer_q.get_single_mut() {
-            Ok((visible_tiles_storage, size, grid_size, map_type)) => {
-                (visible_tiles_storage, size, grid_size, map_type)
-            }
-            Err(_) => {
-                warn!("No visibility layer found");
-                return;
-            }
-        };
-
-    let player_cell = match TilePos::from_world_pos(&player_position, size, grid_size, map_type) {
-        Some(player_cell) => player_cell,
-        None => {
-            warn!("Player outside of map");
-            return;
-        }
-    };
-
-    let occluding_tiles = fov_occluder_tiles_q.iter().copied().collect::<Vec<_>>();
+    // the whole map stays visible while the fov is turned off
+    if !debug_flags.fov {
+        commands.entity(player_entity).remove::<NeedsFovUpdate>();
+        return;
+    }
 
     // clean visible cells
     for cell in player.visible_tiles.iter() {
-        if let Some(cell) = visible_tiles_storage.get(cell) {
-            commands.entity(cell).insert(TileTextureIndex(1));
-        }
+        map.set_visible(cell, false);
     }
     player.visible_tiles.clear(); // clean visible cells
 
@@ -190,128 +344,132 @@ pub fn update_visibile_tiles(
     let cell = IVec2::new(player_cell.x as i32, player_cell.y as i32);
 
     let mut fov_tile_pos: HashSet<TilePos> = HashSet::new();
-    let mut fov_entitites: HashSet<Entity> = HashSet::new();
+    fov_debug.origin = *player_cell;
+    fov_debug.rays.clear();
+    fov_debug.occluders.clear();
 
     for x in -fov_size..=fov_size {
         for y in -fov_size..=fov_size {
             if x == fov_size || x == -fov_size || y == fov_size || y == -fov_size {
-                let end = IVec2::new(cell.x as i32 + x, cell.y as i32 + y);
+                let end = IVec2::new(cell.x + x, cell.y + y);
 
-                if cell.x < 0 || cell.y < 0 || cell.x >= size.x as i32 || cell.y >= size.y as i32 {
-                    return;
-                }
                 // println!("start: {:?} | end: {:?}", cell, end);
-                let path = bresenham_line(cell, end, size);
+                let path = bresenham_line(cell, end, &map.size);
 
+                let mut ray_end = *player_cell;
                 'outer: for cell in path {
-                    if occluding_tiles.contains(&cell) {
+                    ray_end = cell;
+                    if map.is_opaque(&cell) {
                         // info!("cell {:?} is occluded", cell);
+                        fov_debug.occluders.insert(cell);
                         break 'outer;
                     } else {
-                        let cell_visible_e = visible_tiles_storage.get(&cell).unwrap();
-
-                        fov_entitites.insert(cell_visible_e);
                         fov_tile_pos.insert(cell);
                     }
                 }
+                fov_debug.rays.push(ray_end);
             }
         }
     }
-    // println!("fov_entitites: {:?}", fov_entitites.len());
-    for cell in fov_entitites {
-        commands.entity(cell).insert(TileTextureIndex(2));
-    }
-
+    // println!("fov_tile_pos: {:?}", fov_tile_pos.len());
     for cell in fov_tile_pos {
+        map.set_visible(&cell, true);
         player.visible_tiles.push(cell);
     }
 
     commands.entity(player_entity).remove::<NeedsFovUpdate>();
 }
 
-pub fn camera_follow(
-    player_q: Query<(&Transform, &Player), With<Player>>,
-    map_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType), With<TileMapLayer0>>,
-    mut camera_q: Query<&mut Transform, (With<MyGameCamera>, Without<Player>)>,
-    mut lines: ResMut<DebugLines>,
-) {
-    // info!("camera_follow");
-    let (player_transform, _player) = match player_q.get_single() {
-        Ok(player) => player,
-        Err(_) => {
-            warn!("No player found");
-            return;
-        }
-    };
-
-    let mut camera_transform = match camera_q.get_single_mut() {
-        Ok(camera_transform) => camera_transform,
-        Err(_) => {
-            warn!("No camera found");
-            return;
-        }
-    };
-    let (map_size, grid_size, map_type) = map_q.get_single().unwrap();
-
-    let centered_camera_transform =
-        get_tilemap_center_transform(map_size, grid_size, map_type, 0.0);
-
-    // let player_world_pos =
-    //     tile_pos_to_world_pos(player_tile_pos, map_size, grid_size, map_type).unwrap();
-
-    let player_translation = player_transform.translation;
-
-    let transform = centered_camera_transform.with_translation(Vec3::new(
-        player_translation.x,
-        player_translation.y,
-        0.0,
-    ));
-
-    camera_transform.translation = transform.translation;
-
-    lines.line_colored(
-        Vec3::new(-400.0, 0.0, 10.0),
-        Vec3::new(400.0, 0.0, 10.0),
-        0.9,
-        Color::GREEN,
-    );
-
-    lines.line_colored(
-        Vec3::new(0., -400.0, 10.0),
-        Vec3::new(0., 400.0, 10.0),
-        0.9,
-        Color::RED,
-    );
+/// Order of the gameplay systems within a frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TurnSet {
+    /// the player (or the monsters) pick what to do, as intentions
+    Input,
+    /// camera, map chunks and field of view follow the actors
+    View,
+    /// intentions are checked and turned into actions
+    Intentions,
+    /// consequences of the actions: deaths, experience, feedback
+    Resolve,
 }
 
+/// Ends the player's turn: the monsters play next (see `update_enemies`).
 pub fn update_end_turn(
     mut time_system: ResMut<RLTimeSystem>,
     mut end_turn_er: EventReader<TurnEndEvent>,
-    mut game_state: ResMut<State<GameState>>,
+    player_q: Query<(Entity, &StatusEffects), With<Player>>,
+    game_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    if !end_turn_er.is_empty() {
-        println!("end_turn_er");
-        time_system.increment();
-        end_turn_er.clear();
-        if game_state.get() == &GameState::PlayerTurn {
-            *game_state = State::new(GameState::EnemyTurn);
-        } else {
-            *game_state = State::new(GameState::PlayerTurn);
+    if end_turn_er.is_empty() {
+        return;
+    }
+    end_turn_er.clear();
+    // paused or over: the turn ends with the game, and a pending game over
+    // wins over the end of the turn
+    if game_state.get() != &GameState::PlayerTurn || next_state.0.is_some() {
+        return;
+    }
+    // waiting takes as long as a step on the floor
+    if let Ok((player, effects)) = player_q.get_single() {
+        if time_system.is_ready(player) {
+            time_system.schedule_entity(player, effects.scale_time(1));
         }
     }
+    time_system.increment();
+    next_state.set(GameState::EnemyTurn);
 }
 
-pub fn update_enemies(mut game_state: ResMut<State<GameState>>) {}
-
-pub fn update_entities(
+/// Turn start hook of the monsters: the ones in sight of the player walk
+/// toward them, opening the doors on the way. The player plays next.
+pub fn update_enemies(
     time_system: Res<RLTimeSystem>,
-    entities_q: Query<(Entity, &IntentionKind)>,
+    map: Res<GameMap>,
+    player_q: Query<&TilePos, With<Player>>,
+    monsters_q: Query<(Entity, &TilePos, &Transform), With<Monster>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
 ) {
-    let entities = time_system.get_entities_at_current_time();
+    next_state.set(GameState::PlayerTurn);
+    let player_pos = match player_q.get_single() {
+        Ok(player_pos) => *player_pos,
+        Err(_) => return,
+    };
+
+    let mut occupied = monsters_q
+        .iter()
+        .map(|(_, pos, _)| *pos)
+        .collect::<HashSet<_>>();
+    occupied.insert(player_pos);
+    for (monster, monster_pos, transform) in monsters_q.iter() {
+        if !map.is_visible(monster_pos) || !time_system.is_ready(monster) {
+            continue;
+        }
+        // the path starts on the monster, and they don't attack yet: they
+        // stop next to the player
+        let next = match map.find_path(*monster_pos, player_pos) {
+            Some(path) if path.len() > 2 => path[1],
+            _ => continue,
+        };
+        if occupied.contains(&next) {
+            continue;
+        }
 
-    if let Some(entities) = entities {
-        for entity in entities {
-            println!("entity: {:?}", entity);
+        // the door and move actions keep the monster busy for their cost
+        if map.door(&next) == Some(DoorState::Closed) {
+            commands.spawn(DoorIntention {
+                target: next,
+                source: IntentionSourceRef(monster),
+                open: true,
+            });
+            continue;
         }
+        occupied.remove(monster_pos);
+        occupied.insert(next);
+        commands.spawn(MoveIntention {
+            target: next,
+            source: IntentionSourceRef(monster),
+            target_position: map.tile_to_world(&next).extend(transform.translation.z),
+        });
     }
 }
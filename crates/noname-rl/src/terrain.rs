@@ -0,0 +1,104 @@
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::{DoorState, TileKind};
+
+/// Effect applied to an actor stepping on a tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerrainEffect {
+    Damage(i32),
+}
+
+/// TerrainInfo
+///
+/// Static properties of a `TileKind`: movement, sight and look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainInfo {
+    pub name: &'static str,
+    pub walkable: bool,
+    pub transparent: bool,
+    /// game time needed to step on the tile
+    pub movement_cost: u32,
+    pub on_enter: Option<TerrainEffect>,
+    /// kenney sheet indices, one is picked per tile
+    pub sprites: &'static [u32],
+}
+
+impl TerrainInfo {
+    const fn new(name: &'static str, sprites: &'static [u32]) -> Self {
+        Self {
+            name,
+            walkable: true,
+            transparent: true,
+            movement_cost: 1,
+            on_enter: None,
+            sprites,
+        }
+    }
+
+    const fn solid(mut self) -> Self {
+        self.walkable = false;
+        self.transparent = false;
+        self
+    }
+
+    const fn blocks_movement(mut self) -> Self {
+        self.walkable = false;
+        self
+    }
+
+    const fn blocks_sight(mut self) -> Self {
+        self.transparent = false;
+        self
+    }
+
+    const fn cost(mut self, movement_cost: u32) -> Self {
+        self.movement_cost = movement_cost;
+        self
+    }
+
+    const fn on_enter(mut self, effect: TerrainEffect) -> Self {
+        self.on_enter = Some(effect);
+        self
+    }
+
+    /// sprite for the tile at `pos`, stable for a given position
+    pub fn sprite_at(&self, pos: &TilePos) -> u32 {
+        let variant = (pos.x.wrapping_mul(7) ^ pos.y.wrapping_mul(13)) as usize;
+        self.sprites[variant % self.sprites.len()]
+    }
+}
+
+const FLOOR: TerrainInfo = TerrainInfo::new("floor", &[4]);
+const WALL: TerrainInfo = TerrainInfo::new("wall", &[650]).solid();
+const DOOR_OPEN: TerrainInfo = TerrainInfo::new("open door", &[446]);
+const DOOR_CLOSED: TerrainInfo = TerrainInfo::new("closed door", &[444]).solid();
+const DOOR_LOCKED: TerrainInfo = TerrainInfo::new("locked door", &[445]).solid();
+const GRASS: TerrainInfo = TerrainInfo::new("tall grass", &[5, 6, 7]).blocks_sight();
+const WATER: TerrainInfo = TerrainInfo::new("shallow water", &[253]).cost(2);
+const DEEP_WATER: TerrainInfo = TerrainInfo::new("deep water", &[254]).blocks_movement();
+const LAVA: TerrainInfo = TerrainInfo::new("lava", &[301])
+    .cost(2)
+    .on_enter(TerrainEffect::Damage(10));
+const RUBBLE: TerrainInfo = TerrainInfo::new("rubble", &[68, 69]).cost(2);
+const CHASM: TerrainInfo = TerrainInfo::new("chasm", &[0]).blocks_movement();
+const GLASS_WALL: TerrainInfo = TerrainInfo::new("glass wall", &[641]).blocks_movement();
+
+impl TileKind {
+    /// terrain table lookup
+    pub fn terrain(&self) -> TerrainInfo {
+        match self {
+            TileKind::Floor => FLOOR,
+            TileKind::Wall => WALL,
+            TileKind::Door(DoorState::Open) => DOOR_OPEN,
+            TileKind::Door(DoorState::Closed) => DOOR_CLOSED,
+            TileKind::Door(DoorState::Locked { .. }) => DOOR_LOCKED,
+            TileKind::Grass => GRASS,
+            TileKind::Water => WATER,
+            TileKind::DeepWater => DEEP_WATER,
+            TileKind::Lava => LAVA,
+            TileKind::Rubble => RUBBLE,
+            TileKind::Chasm => CHASM,
+            TileKind::GlassWall => GLASS_WALL,
+        }
+    }
+}
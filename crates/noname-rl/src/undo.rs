@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_tweening::Animator;
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    resources::{Dungeon, RLTimeSystem},
+    GameMap, LogCategory, LogMessage, Player, Projectile, RLAction, SaveGame, Targeting, Travel,
+};
+
+/// number of player turns that can be undone
+pub const UNDO_CAPACITY: usize = 20;
+
+/// The game as it was at the start of a player turn.
+#[derive(Clone)]
+pub struct UndoSnapshot {
+    pub map: GameMap,
+    pub time: RLTimeSystem,
+    pub actors: SaveGame,
+}
+
+/// UndoHistory
+///
+/// Ring buffer of the last `UNDO_CAPACITY` player turns. Only inserted in
+/// debug builds, see `main`.
+#[derive(Resource, Default)]
+pub struct UndoHistory {
+    snapshots: VecDeque<UndoSnapshot>,
+}
+
+impl UndoHistory {
+    /// keeps one snapshot per turn: resuming a paused game enters the same
+    /// turn again
+    pub fn push(&mut self, snapshot: UndoSnapshot) {
+        let turn = snapshot.time.get_time();
+        if let Some(last) = self.snapshots.back() {
+            if last.time.get_time() >= turn {
+                self.snapshots.pop_back();
+            }
+        }
+        if self.snapshots.len() >= UNDO_CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// the start of the turn before `turn` (or the oldest one kept), dropping
+    /// the snapshots taken after it
+    pub fn rewind(&mut self, turn: u32) -> Option<UndoSnapshot> {
+        let index = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.time.get_time() < turn)
+            .unwrap_or(0);
+        self.snapshots.truncate(index + 1);
+        self.snapshots.back().cloned()
+    }
+}
+
+/// Snapshots the game when a player turn starts.
+pub fn take_undo_snapshot(world: &mut World) {
+    let actors = match SaveGame::capture(world) {
+        Some(actors) => actors,
+        None => return,
+    };
+    let snapshot = UndoSnapshot {
+        map: world.resource::<GameMap>().clone(),
+        time: world.resource::<RLTimeSystem>().clone(),
+        actors,
+    };
+    world.resource_mut::<UndoHistory>().push(snapshot);
+}
+
+/// Rewinds the game to the start of the previous player turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UndoAction;
+
+impl Command for UndoAction {
+    fn apply(self, world: &mut World) {
+        let turn = world.resource::<RLTimeSystem>().get_time();
+        let snapshot = match world.resource_mut::<UndoHistory>().rewind(turn) {
+            Some(snapshot) => snapshot,
+            None => {
+                info!("nothing to undo");
+                return;
+            }
+        };
+        let restored = snapshot.time.get_time();
+        info!("undo: back from turn {} to turn {}", turn, restored);
+
+        // projectiles in flight would hit monsters that are gone
+        let projectiles = world
+            .query_filtered::<Entity, With<Projectile>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for entity in projectiles {
+            world.entity_mut(entity).despawn_recursive();
+        }
+        // nor should the player finish the move that was undone
+        let players = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for entity in players {
+            world.entity_mut(entity).remove::<Animator<Transform>>();
+        }
+
+        let mut map = snapshot.map;
+        // the tilemap and the minimap are redrawn from the restored map
+        map.mark_all_dirty();
+        world.insert_resource(map);
+        world.insert_resource(snapshot.time);
+        world.resource_mut::<Dungeon>().depth = snapshot.actors.depth;
+        snapshot.actors.restore_actors(world);
+
+        // they refer to the monsters that were just replaced
+        world.resource_mut::<Travel>().stop();
+        world.resource_mut::<Targeting>().cancel();
+
+        LogMessage::new(
+            LogCategory::Info,
+            format!("undo: back to turn {}", restored),
+        )
+        .apply(world);
+    }
+}
+
+pub fn undo_input(action_q: Query<&ActionState<RLAction>, With<Player>>, mut commands: Commands) {
+    let pressed = action_q
+        .iter()
+        .any(|action| action.just_pressed(RLAction::Undo));
+    if pressed {
+        commands.add(UndoAction);
+    }
+}